@@ -0,0 +1,138 @@
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+const TOKEN_USERNAME: &str = "x-access-token";
+
+fn credential_input(host: &str, token: Option<&str>) -> String {
+    let mut input = format!("protocol=https\nhost={}\nusername={}\n", host, TOKEN_USERNAME);
+    if let Some(token) = token {
+        input.push_str(&format!("password={}\n", token));
+    }
+    input.push('\n');
+    input
+}
+
+// Runs `git credential <action>`, which delegates to the configured credential helpers,
+// e.g. osxkeychain, wincred or libsecret
+fn git_credential(action: &str, input: &str) -> Result<String, String> {
+    let mut child = Command::new("git")
+        .arg("credential")
+        .arg(action)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("cannot run git credential {}: {}", action, e))?;
+
+    child.stdin.take().unwrap().write_all(input.as_bytes())
+        .map_err(|e| format!("cannot write to git credential {}: {}", action, e))?;
+
+    let output = child.wait_with_output().map_err(|e| format!("git credential {} failed: {}", action, e))?;
+    if !output.status.success() {
+        return Err(format!("git credential {} exited with {}", action, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn env_token(host: &str) -> Option<String> {
+    let provider_var = match host {
+        "github.com" => Some("GITHUB_TOKEN"),
+        "gitlab.com" => Some("GITLAB_TOKEN"),
+        _ => None
+    };
+    env::var("GIT_PULL_REQUESTS_TOKEN").ok()
+        .or_else(|| provider_var.and_then(|v| env::var(v).ok()))
+}
+
+fn helper_token(host: &str) -> Option<String> {
+    let output = match git_credential("fill", &credential_input(host, None)) {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("{}", e);
+            return None;
+        }
+    };
+    output.lines()
+        .filter(|l| l.starts_with("password="))
+        .map(|l| l["password=".len()..].to_string())
+        .next()
+}
+
+pub fn host_of_url(url: &str) -> Option<&str> {
+    let rest = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => return None
+    };
+    let rest = match rest.find('@') {
+        Some(i) => &rest[i + 1..],
+        None => rest
+    };
+    rest.split(['/', ':']).next()
+}
+
+pub fn token(host: &str) -> Option<String> {
+    env_token(host).or_else(|| helper_token(host))
+}
+
+// Turns terminal echo off or back on while the token is typed
+fn set_echo(on: bool) -> Result<(), String> {
+    let status = Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(Stdio::inherit())
+        .status()
+        .map_err(|e| format!("cannot run stty: {}", e))?;
+    if !status.success() {
+        return Err(format!("stty exited with {}", status));
+    }
+    Ok(())
+}
+
+fn read_token() -> Result<String, String> {
+    let stdin = io::stdin();
+    let mut token = String::new();
+    if !stdin.is_terminal() {
+        stdin.lock().read_line(&mut token).map_err(|e| format!("cannot read token: {}", e))?;
+        return Ok(token);
+    }
+
+    set_echo(false)?;
+    let read = stdin.lock().read_line(&mut token);
+    let restored = set_echo(true);
+    println!();
+    read.map_err(|e| format!("cannot read token: {}", e))?;
+    restored?;
+    Ok(token)
+}
+
+// git credential approve succeeds even when no helper is configured or none of them stores
+// anything, so the token is looked up again to check that it was stored
+pub fn login(host: &str) -> Result<(), String> {
+    print!("Token for {}: ", host);
+    io::stdout().flush().map_err(|e| format!("cannot write to stdout: {}", e))?;
+
+    let token = read_token()?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err("empty token".into());
+    }
+
+    git_credential("approve", &credential_input(host, Some(token)))?;
+    if helper_token(host).as_ref().map(|t| &t[..]) != Some(token) {
+        return Err(format!("no git credential helper stored the token for {}; configure one with git config credential.helper", host));
+    }
+    println!("Token for {} stored with git credential helpers", host);
+    Ok(())
+}
+
+pub fn status(host: &str) -> Result<(), String> {
+    if env_token(host).is_some() {
+        println!("{}: token provided by environment", host);
+    } else if helper_token(host).is_some() {
+        println!("{}: token provided by git credential helpers", host);
+    } else {
+        println!("{}: no token found; run `git-pull-requests auth login --host {}`", host, host);
+    }
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use git2::{self, Oid, Repository};
+
+use {PullRequestInfo, Provider};
+
+// The commits which picking the pull request takes, oldest first, with the parent their changes
+// are taken relative to: merges are picked against their first parent, like `cherry-pick -m 1`,
+// and patch series commit by commit
+fn commits_to_pick(repo: &Repository, pr: &PullRequestInfo) -> Result<Vec<(Oid, u32)>, git2::Error> {
+    let tip = Oid::from_str(&pr.sha)?;
+    match (pr.provider, pr.commits.first()) {
+        (Provider::SourceHut, Some(first)) => {
+            let mut walk = repo.revwalk()?;
+            walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+            walk.push(tip)?;
+            for parent in repo.find_commit(Oid::from_str(&first.sha)?)?.parent_ids() {
+                walk.hide(parent)?;
+            }
+            walk.map(|oid| oid.map(|oid| (oid, 0))).collect()
+        }
+        _ => {
+            let mainline = if repo.find_commit(tip)?.parent_count() > 1 { 1 } else { 0 };
+            Ok(vec![(tip, mainline)])
+        }
+    }
+}
+
+// Messages get a line naming the original commit, like with `cherry-pick -x`, which also keeps
+// the pull request header, so that the pull request is known to be on the branch afterwards
+fn cherry_pick_message(commit: &git2::Commit) -> String {
+    format!("{}\n\n(cherry picked from commit {})\n", String::from_utf8_lossy(commit.message_bytes()).trim_end(), commit.id())
+}
+
+// Cherry-picks the pull requests onto the branch in the order they are given, in memory, so that
+// the branch is only moved when all of them apply cleanly; a checked out branch has its working
+// tree updated, which fails rather than overwriting local changes. Pull requests whose changes are
+// already on the branch are skipped; returns the ones which were picked
+pub fn cherry_pick<'a>(repo: &Repository, branch: &str, pull_requests: &[&'a PullRequestInfo], dry_run: bool)
+    -> Result<Vec<&'a PullRequestInfo>, String>
+{
+    let ref_name = format!("refs/heads/{}", branch);
+    let mut reference = repo.find_reference(&ref_name).map_err(|e| format!("cannot find branch {}: {}", branch, e))?;
+    let mut tip = reference.peel_to_commit().map_err(|e| format!("cannot resolve {}: {}", branch, e))?;
+    let committer = repo.signature().map_err(|e| format!("cannot determine the committer: {}", e))?;
+
+    let mut picked = Vec::new();
+    for &pr in pull_requests {
+        let before = tip.id();
+        let commits = commits_to_pick(repo, pr).map_err(|e| format!("cannot find the commits of {}: {}", pr.reference(), e))?;
+        for (oid, mainline) in commits {
+            let commit = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+            let mut index = repo.cherrypick_commit(&commit, &tip, mainline, None)
+                .map_err(|e| format!("cannot cherry-pick {} of {}: {}", oid, pr.reference(), e))?;
+            if index.has_conflicts() {
+                let mut paths: Vec<String> = index.conflicts().map_err(|e| format!("cannot list conflicts: {}", e))?
+                    .filter_map(|c| c.ok())
+                    .filter_map(|c| c.our.or(c.their).or(c.ancestor))
+                    .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+                    .collect();
+                paths.dedup();
+                return Err(format!("{} conflicts with {} in {}, {} is left unchanged",
+                                   pr.reference(), branch, paths.join(", "), branch));
+            }
+            let tree = index.write_tree_to(repo).and_then(|oid| repo.find_tree(oid))
+                .map_err(|e| format!("cannot write the tree of {}: {}", pr.reference(), e))?;
+            if tree.id() == tip.tree_id() {
+                continue;
+            }
+            tip = repo.commit(None, &commit.author(), &committer, &cherry_pick_message(&commit), &tree, &[&tip])
+                .and_then(|oid| repo.find_commit(oid))
+                .map_err(|e| format!("cannot commit {}: {}", pr.reference(), e))?;
+        }
+        if tip.id() == before {
+            warn!("{} is already on {}, skipping it", pr.reference(), branch);
+        } else {
+            picked.push(pr);
+        }
+    }
+    if dry_run || picked.is_empty() {
+        return Ok(picked);
+    }
+
+    let checked_out = !repo.is_bare() && repo.head().ok().and_then(|h| h.name().map(|n| n == ref_name)).unwrap_or(false);
+    if checked_out {
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout.safe();
+        repo.checkout_tree(tip.as_object(), Some(&mut checkout))
+            .map_err(|e| format!("cannot update the working tree of {}: {}", branch, e))?;
+    }
+    let message = format!("backport: {}", picked.iter().map(|pr| pr.reference()).collect::<Vec<_>>().join(", "));
+    reference.set_target(tip.id(), &message).map_err(|e| format!("cannot update {}: {}", branch, e))?;
+    Ok(picked)
+}
+
+// A selection of items numbered from 1 like "1 3-5", "2,4" or "all"
+pub fn parse_selection(text: &str, count: usize) -> Result<Vec<usize>, String> {
+    if text.trim() == "all" {
+        return Ok((1..=count).collect());
+    }
+    let mut result = Vec::new();
+    for part in text.split(|c: char| c == ',' || c.is_whitespace()).filter(|p| !p.is_empty()) {
+        let number = |s: &str| match s.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= count => Ok(n),
+            _ => Err(format!("invalid selection {}, expected numbers from 1 to {}", part, count))
+        };
+        let (from, to) = match part.split_once('-') {
+            Some((from, to)) => (number(from)?, number(to)?),
+            None => (number(part)?, number(part)?)
+        };
+        result.extend(from..=to);
+    }
+    result.sort();
+    result.dedup();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    fn commit(repo: &Repository, message: &str, parents: &[Oid], files: &[(&str, &str)]) -> Oid {
+        let mut index = git2::Index::new().unwrap();
+        if let Some(&parent) = parents.first() {
+            index.read_tree(&repo.find_commit(parent).unwrap().tree().unwrap()).unwrap();
+        }
+        for &(path, content) in files {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            let entry = git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0), mtime: git2::IndexTime::new(0, 0), dev: 0, ino: 0, mode: 0o100644,
+                uid: 0, gid: 0, file_size: content.len() as u32, id: blob, flags: path.len() as u16, flags_extended: 0,
+                path: path.as_bytes().to_vec()
+            };
+            index.add(&entry).unwrap();
+        }
+        let tree = repo.find_tree(index.write_tree_to(repo).unwrap()).unwrap();
+        let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let parents: Vec<_> = parents.iter().map(|&p| repo.find_commit(p).unwrap()).collect();
+        repo.commit(None, &signature, &signature, message, &tree, &parents.iter().collect::<Vec<_>>()).unwrap()
+    }
+
+    fn merged(id: u32, sha: Oid) -> PullRequestInfo {
+        PullRequestInfo {
+            provider: Provider::GitHub,
+            id,
+            sha: sha.to_string(),
+            time: 0,
+            author: "alice".to_string(),
+            branch: "fix".to_string(),
+            name: String::new(),
+            body: String::new(),
+            commits: vec![],
+            first_release: None,
+            author_check_failed: false,
+            excerpt: None,
+            labels: vec![],
+            milestone: None,
+            merged_by: None,
+            author_association: None,
+            reviewers: None,
+            email: None,
+            reverted_by: None,
+            url: None,
+            category: None
+        }
+    }
+
+    fn read(repo: &Repository, oid: Oid, path: &str) -> String {
+        let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+        let blob = repo.find_blob(tree.get_path(Path::new(path)).unwrap().id()).unwrap();
+        String::from_utf8(blob.content().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn cherry_picks_merges_onto_branches() {
+        let dir = env::temp_dir().join(format!("git-pull-requests-backport-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init_bare(&dir).unwrap();
+        repo.config().unwrap().set_str("user.name", "Bob").unwrap();
+        repo.config().unwrap().set_str("user.email", "bob@example.com").unwrap();
+
+        let base = commit(&repo, "init", &[], &[("a", "1\n"), ("b", "1\n")]);
+        let fix = commit(&repo, "fix a", &[base], &[("a", "2\n")]);
+        let main = commit(&repo, "Merge pull request #1 from alice/fix\n\nFix a", &[base, fix], &[("a", "2\n")]);
+        let change = commit(&repo, "change b", &[main], &[("b", "2\n")]);
+        let main = commit(&repo, "Merge pull request #2 from alice/change", &[main, change], &[("a", "2\n"), ("b", "2\n")]);
+        let release = commit(&repo, "release", &[base], &[("c", "1\n")]);
+        repo.reference("refs/heads/release", release, true, "test").unwrap();
+        repo.reference("refs/heads/main", main, true, "test").unwrap();
+
+        let (first, second) = (merged(1, repo.find_commit(main).unwrap().parent_id(0).unwrap()), merged(2, main));
+        assert_eq!(cherry_pick(&repo, "release", &[&first, &second], true).unwrap().len(), 2);
+        assert_eq!(repo.refname_to_id("refs/heads/release").unwrap(), release);
+
+        assert_eq!(cherry_pick(&repo, "release", &[&first, &second], false).unwrap().len(), 2);
+        let tip = repo.refname_to_id("refs/heads/release").unwrap();
+        assert_eq!((read(&repo, tip, "a"), read(&repo, tip, "b"), read(&repo, tip, "c")), ("2\n".into(), "2\n".into(), "1\n".into()));
+        let picked = repo.find_commit(tip).unwrap();
+        assert_eq!(picked.parent_count(), 1);
+        assert_eq!(picked.author().name(), Some("Alice"));
+        assert_eq!(picked.committer().name(), Some("Bob"));
+        assert_eq!(picked.message(), Some(&format!("Merge pull request #2 from alice/change\n\n(cherry picked from commit {})\n", main)[..]));
+
+        assert!(cherry_pick(&repo, "release", &[&first], false).unwrap().is_empty());
+        assert_eq!(repo.refname_to_id("refs/heads/release").unwrap(), tip);
+
+        let conflicting = commit(&repo, "change a", &[release], &[("a", "3\n")]);
+        repo.reference("refs/heads/release", conflicting, true, "test").unwrap();
+        assert_eq!(cherry_pick(&repo, "release", &[&second, &first], false).err(),
+                   Some("#1 conflicts with release in a, release is left unchanged".to_string()));
+        assert_eq!(repo.refname_to_id("refs/heads/release").unwrap(), conflicting);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_selections() {
+        assert_eq!(parse_selection("1 3-5,2", 5), Ok(vec![1, 2, 3, 4, 5]));
+        assert_eq!(parse_selection(" all ", 3), Ok(vec![1, 2, 3]));
+        assert_eq!(parse_selection("4 4", 5), Ok(vec![4]));
+        assert_eq!(parse_selection("", 5), Ok(vec![]));
+        assert_eq!(parse_selection("2 6", 5), Err("invalid selection 6, expected numbers from 1 to 5".to_string()));
+        assert!(parse_selection("x", 5).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use forge::{self, Details};
+use {Provider, PullRequestInfo};
+
+// Bitbucket Cloud
+pub struct Bitbucket {
+    repository: String,
+    token: Option<String>
+}
+
+impl Bitbucket {
+    pub fn new(repository: &str, token: Option<String>) -> Bitbucket {
+        Bitbucket { repository: repository.to_string(), token }
+    }
+}
+
+impl forge::Forge for Bitbucket {
+    fn repository(&self) -> String {
+        format!("bitbucket.org/{}", self.repository)
+    }
+
+    fn handles(&self, pr: &PullRequestInfo) -> bool {
+        pr.provider == Provider::Bitbucket
+    }
+
+    fn pull_request_url(&self, id: u32) -> String {
+        format!("https://bitbucket.org/{}/pull-requests/{}", self.repository, id)
+    }
+
+    // Bitbucket puts the newer revision first
+    fn compare_url(&self, from: &str, to: &str) -> String {
+        format!("https://bitbucket.org/{}/branches/compare/{}%0D{}", self.repository, to, from)
+    }
+
+    // Bitbucket has neither labels nor milestones
+    fn fetch_pull_request(&self, id: u32) -> Result<Details, String> {
+        let url = format!("https://api.bitbucket.org/2.0/repositories/{}/pullrequests/{}", self.repository, id);
+        let headers: Vec<String> = self.token.iter().map(|t| format!("Authorization: Bearer {}", t)).collect();
+        let json = forge::request(&url, &headers, None)?;
+
+        Ok(Details {
+            title: forge::non_empty(json.get("title").and_then(|t| t.as_str())),
+            merged_by: json.pointer("/closed_by/nickname").and_then(|n| n.as_str()).map(|n| n.to_string()),
+            ..Details::default()
+        })
+    }
+
+    // Approvals are part of the pull request, as participants who approved it
+    fn fetch_reviewers(&self, id: u32) -> Result<Vec<String>, String> {
+        let url = format!("https://api.bitbucket.org/2.0/repositories/{}/pullrequests/{}", self.repository, id);
+        let headers: Vec<String> = self.token.iter().map(|t| format!("Authorization: Bearer {}", t)).collect();
+        let json = forge::request(&url, &headers, None)?;
+        Ok(json.get("participants").and_then(|p| p.as_array()).map_or(vec![], |participants| {
+            participants.iter()
+                .filter(|p| p.get("approved").and_then(|a| a.as_bool()) == Some(true))
+                .filter_map(|p| p.pointer("/user/nickname").and_then(|n| n.as_str()))
+                .map(|n| n.to_string()).collect()
+        }))
+    }
+}
+
+// Bitbucket names the branch, prefixed with the repository for pull requests from forks, as in
+// `Merged in user/repo:branch (pull request #12)`, and puts the title into the body
+pub fn parse_header(header: &str) -> Option<Result<(u32, String), String>> {
+    let header_pattern = regex!(r"^Merged in (?:\S+:)?(\S+) \(pull request #(\d+)\)$");
+    header_pattern.captures(header.trim()).map(|captures| match captures[2].parse() {
+        Ok(id) => Ok((id, captures[1].to_string())),
+        Err(e) => Err(format!("invalid pull request id {}: {}", &captures[2], e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bitbucket_headers() {
+        assert_eq!(parse_header("Merged in fix-parser (pull request #7)"), Some(Ok((7, "fix-parser".to_string()))));
+        assert_eq!(parse_header("Merged in alice/project:fix-parser (pull request #7)"),
+                   Some(Ok((7, "fix-parser".to_string()))));
+        assert_eq!(parse_header("Merged in fix-parser"), None);
+    }
+}
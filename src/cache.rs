@@ -0,0 +1,113 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use paths::cache_dir;
+
+fn mirrors_dir() -> Result<PathBuf, String> {
+    cache_dir().map(|d| d.join("mirrors")).ok_or_else(|| "cannot determine cache directory".to_string())
+}
+
+pub fn mirror_path(url: &str) -> Option<PathBuf> {
+    let name: String = url.chars().map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+    cache_dir().map(|d| d.join("mirrors").join(name))
+}
+
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+    }
+    Ok(size)
+}
+
+// FETCH_HEAD is rewritten on every fetch, so its modification time tells when the mirror was last used
+fn last_used(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path.join("FETCH_HEAD"))
+        .or_else(|_| fs::metadata(path))
+        .and_then(|m| m.modified())
+}
+
+fn entries() -> Result<Vec<Entry>, String> {
+    let dir = mirrors_dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let read_dir = fs::read_dir(&dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+    let mut result = Vec::new();
+    for entry in read_dir {
+        let path = entry.map_err(|e| format!("cannot read {}: {}", dir.display(), e))?.path();
+        let size = dir_size(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let last_used = last_used(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        result.push(Entry {
+            path,
+            size,
+            last_used
+        });
+    }
+    result.sort_by_key(|a| a.last_used);
+    Ok(result)
+}
+
+fn remove(entry: &Entry) -> Result<(), String> {
+    info!("Removing {}", entry.path.display());
+    fs::remove_dir_all(&entry.path).map_err(|e| format!("cannot remove {}: {}", entry.path.display(), e))
+}
+
+fn age_days(entry: &Entry) -> u64 {
+    SystemTime::now().duration_since(entry.last_used).map(|d| d.as_secs() / 86400).unwrap_or(0)
+}
+
+pub fn list() -> Result<(), String> {
+    let entries = entries()?;
+    for entry in &entries {
+        println!("{}\t{} KiB\t{} days", entry.path.display(), entry.size / 1024, age_days(entry));
+    }
+    let total = entries.iter().fold(0, |acc, e| acc + e.size);
+    println!("Total: {} mirrors, {} KiB", entries.len(), total / 1024);
+    Ok(())
+}
+
+pub fn clear() -> Result<(), String> {
+    for entry in entries()?.iter() {
+        remove(entry)?;
+    }
+    Ok(())
+}
+
+pub fn gc(max_age_days: u64, max_size_mb: Option<u64>) -> Result<(), String> {
+    let max_age = Duration::from_secs(max_age_days * 86400);
+    let mut remaining = Vec::new();
+    for entry in entries()? {
+        let expired = SystemTime::now().duration_since(entry.last_used).map(|d| d > max_age).unwrap_or(false);
+        if expired {
+            remove(&entry)?;
+        } else {
+            remaining.push(entry);
+        }
+    }
+
+    if let Some(max_size_mb) = max_size_mb {
+        let max_size = max_size_mb * 1024 * 1024;
+        let mut total = remaining.iter().fold(0, |acc, e| acc + e.size);
+        // entries are sorted from least recently used
+        for entry in &remaining {
+            if total <= max_size {
+                break;
+            }
+            remove(entry)?;
+            total -= entry.size;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use ctrlc;
+use time;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// Milliseconds on the precise_time_ns clock, zero if there is no timeout
+static DEADLINE_MS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stop {
+    Interrupted,
+    TimedOut
+}
+
+impl Stop {
+    pub fn description(self) -> &'static str {
+        match self {
+            Stop::Interrupted => "interrupted",
+            Stop::TimedOut => "timed out"
+        }
+    }
+
+    // Same codes as a shell reports for SIGINT and as timeout(1) uses
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Stop::Interrupted => 130,
+            Stop::TimedOut => 124
+        }
+    }
+}
+
+fn now_ms() -> usize {
+    (time::precise_time_ns() / 1_000_000) as usize
+}
+
+// The first Ctrl-C asks long running loops to stop so that partial output can still be
+// written; the second one exits immediately
+pub fn install(timeout_secs: Option<u64>) {
+    if let Some(secs) = timeout_secs {
+        DEADLINE_MS.store(now_ms() + secs as usize * 1000, Ordering::SeqCst);
+    }
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(Stop::Interrupted.exit_code());
+        }
+    });
+    if let Err(e) = result {
+        warn!("cannot install Ctrl-C handler: {}", e);
+    }
+}
+
+pub fn stopped() -> Option<Stop> {
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        return Some(Stop::Interrupted);
+    }
+    let deadline = DEADLINE_MS.load(Ordering::SeqCst);
+    if deadline != 0 && now_ms() >= deadline {
+        return Some(Stop::TimedOut);
+    }
+    None
+}
@@ -0,0 +1,125 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// The new release goes right before the first existing `## ` section, so that a title and an
+// introduction at the top of the file stay where they are
+fn insertion_point(content: &str) -> usize {
+    let mut offset = 0;
+    for line in content.split('\n') {
+        if line.starts_with("## ") {
+            return offset;
+        }
+        offset += line.len() + 1;
+    }
+    content.len()
+}
+
+// The section of an earlier run for the same release, from its heading to the next section
+fn existing_section(content: &str, release_name: &str) -> Option<(usize, usize)> {
+    let heading = format!("## {}", release_name);
+    let mut offset = 0;
+    let mut start = None;
+    for line in content.split('\n') {
+        if line.starts_with("## ") {
+            if start.is_some() {
+                return start.map(|start| (start, offset));
+            }
+            if line.trim_end() == heading || line.starts_with(&format!("{} - ", heading)) {
+                start = Some(offset);
+            }
+        }
+        offset += line.len() + 1;
+    }
+    start.map(|start| (start, content.len()))
+}
+
+// Replaces the section of the release if there is one already, so that generating the notes
+// again doesn't duplicate it, and adds a new one otherwise
+fn update(content: &str, release_name: &str, release_date: &str, text: &str) -> String {
+    let (at, end) = existing_section(content, release_name).unwrap_or_else(|| {
+        let at = insertion_point(content);
+        (at, at)
+    });
+    let mut result = String::with_capacity(content.len() + text.len() + 64);
+    result.push_str(&content[..at]);
+    if !result.is_empty() && !result.ends_with("\n\n") {
+        result.push_str(if result.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+    result.push_str(&format!("## {} - {}\n\n", release_name, release_date));
+    result.push_str(text.trim_matches('\n'));
+    result.push_str("\n\n");
+    result.push_str(&content[end..]);
+    format!("{}\n", result.trim_end_matches('\n'))
+}
+
+pub fn insert(path: &Path, release_name: &str, release_date: &str, text: &str) -> Result<(), String> {
+    let mut content = String::new();
+    match File::open(path) {
+        Ok(mut f) => {
+            f.read_to_string(&mut content).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => content.push_str("# Changelog\n\n"),
+        Err(e) => return Err(format!("cannot open {}: {}", path.display(), e))
+    }
+    let result = update(&content, release_name, release_date, text);
+
+    // Writing to a temporary file first keeps the old changelog intact if anything fails
+    let tmp_path = path.with_extension("md.tmp");
+    File::create(&tmp_path).and_then(|mut f| f.write_all(result.as_bytes()))
+        .map_err(|e| format!("cannot write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("cannot replace {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: &str = "* Support tabs (#12)\n";
+
+    #[test]
+    fn starts_a_new_changelog() {
+        assert_eq!(update("# Changelog\n\n", "v1.0.0", "2024-03-01", NOTES),
+                   "# Changelog\n\n## v1.0.0 - 2024-03-01\n\n* Support tabs (#12)\n");
+        assert_eq!(update("", "v1.0.0", "2024-03-01", NOTES), "## v1.0.0 - 2024-03-01\n\n* Support tabs (#12)\n");
+    }
+
+    #[test]
+    fn puts_releases_above_earlier_ones() {
+        let content = "# Changelog\n\nAll notable changes.\n## v1.0.0 - 2024-03-01\n\n* Initial release\n";
+        assert_eq!(update(content, "v1.1.0", "2024-04-01", NOTES), "\
+# Changelog
+
+All notable changes.
+
+## v1.1.0 - 2024-04-01
+
+* Support tabs (#12)
+
+## v1.0.0 - 2024-03-01
+
+* Initial release
+");
+    }
+
+    #[test]
+    fn replaces_sections_of_the_same_release() {
+        let content = "# Changelog\n\n## v1.1.0 - 2024-03-31\n\n* Draft\n\n## v1.0.0 - 2024-03-01\n\n* Initial release\n";
+        assert_eq!(update(content, "v1.1.0", "2024-04-01", NOTES), "\
+# Changelog
+
+## v1.1.0 - 2024-04-01
+
+* Support tabs (#12)
+
+## v1.0.0 - 2024-03-01
+
+* Initial release
+");
+        // A release which is only a prefix of another one's name is a different release
+        assert_eq!(update("## v1.1.0-rc.1 - 2024-03-31\n\n* Draft\n", "v1.1.0", "2024-04-01", NOTES),
+                   "## v1.1.0 - 2024-04-01\n\n* Support tabs (#12)\n\n## v1.1.0-rc.1 - 2024-03-31\n\n* Draft\n");
+        assert_eq!(update("## v1.0.0 - 2024-03-01\n\n## v1.1.0\n\n* Draft\n", "v1.1.0", "2024-04-01", NOTES),
+                   "## v1.0.0 - 2024-03-01\n\n## v1.1.0 - 2024-04-01\n\n* Support tabs (#12)\n");
+    }
+}
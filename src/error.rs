@@ -0,0 +1,40 @@
+use std::fmt;
+
+// Fatal errors by the stage they happened in, so that scripts can tell them apart
+// by the exit code
+#[derive(Debug)]
+pub enum Error {
+    Usage(String),
+    Repository(String),
+    Revwalk(String),
+    Parse(String),
+    Io(String),
+    Empty(String)
+}
+
+impl Error {
+    // 2 is also what clap exits with on invalid arguments
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Usage(_) => 2,
+            Error::Repository(_) => 3,
+            Error::Revwalk(_) => 4,
+            Error::Parse(_) => 5,
+            Error::Io(_) => 6,
+            Error::Empty(_) => 7
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(ref message) |
+            Error::Repository(ref message) |
+            Error::Revwalk(ref message) |
+            Error::Parse(ref message) |
+            Error::Io(ref message) |
+            Error::Empty(ref message) => f.write_str(message)
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use git2::{self, Repository};
+use regex::Regex;
+
+use {PullRequestInfo, parallel_chunks, pull_request_diff};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    Added,
+    Removed
+}
+
+impl Change {
+    pub fn description(self) -> &'static str {
+        match self {
+            Change::Added => "Added",
+            Change::Removed => "Removed"
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Scope {
+    Diff,
+    Body
+}
+
+pub struct Rule {
+    change: Change,
+    scope: Scope,
+    pattern: Regex
+}
+
+// Rule files contain `<added|removed> <diff|body> <regex>` lines; the first capture group of
+// the regex, or the whole match if there is none, is the flag name. Diff rules are matched
+// against added or removed lines of the pull request diff, depending on the kind of change
+pub fn load(path: &Path) -> Result<Vec<Rule>, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+    let mut result = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(3, ' ').collect();
+        if parts.len() != 3 {
+            return Err(format!("{}:{}: expected `<added|removed> <diff|body> <regex>`", path.display(), i + 1));
+        }
+        let change = match parts[0] {
+            "added" => Change::Added,
+            "removed" => Change::Removed,
+            other => return Err(format!("{}:{}: unknown change kind: {}", path.display(), i + 1, other))
+        };
+        let scope = match parts[1] {
+            "diff" => Scope::Diff,
+            "body" => Scope::Body,
+            other => return Err(format!("{}:{}: unknown rule scope: {}", path.display(), i + 1, other))
+        };
+        let pattern = Regex::new(parts[2].trim())
+            .map_err(|e| format!("{}:{}: invalid regex: {}", path.display(), i + 1, e))?;
+        result.push(Rule { change, scope, pattern });
+    }
+    Ok(result)
+}
+
+fn flag_name(pattern: &Regex, text: &str) -> Option<String> {
+    pattern.captures(text).and_then(|captures| captures.get(1).or_else(|| captures.get(0))).map(|m| m.as_str().to_string())
+}
+
+fn diff_lines(repo: &Repository, sha: &str) -> Result<Vec<(char, String)>, git2::Error> {
+    let diff = pull_request_diff(repo, sha)?;
+
+    let mut lines = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        match line.origin() {
+            '+' | '-' => lines.push((line.origin(), String::from_utf8_lossy(line.content()).into_owned())),
+            _ => {}
+        }
+        true
+    })?;
+    Ok(lines)
+}
+
+// Maps every changed flag to the ids of the pull requests changing it; a flag which is both
+// added and removed by the same pull request has most likely just been moved and is ignored.
+// Diffs are computed on `jobs` threads, each with its own repository
+pub fn extract(repo: &Repository, pull_requests: &[PullRequestInfo], rules: &[Rule], jobs: usize)
+    -> Result<BTreeMap<(Change, String), Vec<String>>, String>
+{
+    let needs_diff = rules.iter().any(|r| r.scope == Scope::Diff);
+    let diffs = if needs_diff {
+        let repo_path = repo.path().to_path_buf();
+        let shas: Vec<String> = pull_requests.iter().map(|pr| pr.sha.clone()).collect();
+        parallel_chunks(shas, jobs, move |chunk| {
+            let repo = match Repository::open(&repo_path) {
+                Ok(repo) => repo,
+                Err(e) => return chunk.iter().map(|_| Err(format!("cannot open repository: {}", e))).collect()
+            };
+            chunk.iter().map(|sha| diff_lines(&repo, sha).map_err(|e| format!("cannot get diff of {}: {}", sha, e))).collect()
+        })
+    } else {
+        pull_requests.iter().map(|_| Ok(Vec::new())).collect()
+    };
+
+    let mut result = BTreeMap::new();
+    for (pr, lines) in pull_requests.iter().zip(diffs) {
+        let lines = lines?;
+
+        let mut changes = Vec::new();
+        for rule in rules {
+            let origin = if rule.change == Change::Added { '+' } else { '-' };
+            match rule.scope {
+                Scope::Body => changes.extend(pr.body.lines().filter_map(|l| flag_name(&rule.pattern, l))
+                                              .map(|name| (rule.change, name))),
+                Scope::Diff => changes.extend(lines.iter().filter(|&&(o, _)| o == origin)
+                                              .filter_map(|(_, l)| flag_name(&rule.pattern, l))
+                                              .map(|name| (rule.change, name)))
+            }
+        }
+        changes.sort();
+        changes.dedup();
+
+        for &(change, ref name) in &changes {
+            let opposite = if change == Change::Added { Change::Removed } else { Change::Added };
+            if changes.iter().any(|&(c, ref n)| c == opposite && n == name) {
+                continue;
+            }
+            result.entry((change, name.clone())).or_insert_with(Vec::new)
+                .push(pr.reference());
+        }
+    }
+    Ok(result)
+}
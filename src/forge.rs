@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+use std::sync::Arc;
+
+use serde_json::{self, Value};
+
+use auth;
+use bitbucket::Bitbucket;
+use cancel;
+use gitea::Gitea;
+use github::{self, GitHub};
+use gitlab::GitLab;
+use paths::state_dir;
+use replay;
+use {PullRequestInfo, parallel_chunks};
+
+// A forge which pull request details can be fetched from and which the notes link to; GitHub,
+// GitLab, Gitea and Bitbucket are built in, other forges can be supported by passing an
+// implementation to `enrich` and adding it to `Config::forges`
+pub trait Forge: Send + Sync {
+    // Identifies the repository in messages and --resume progress files, e.g. github.com/owner/repo
+    fn repository(&self) -> String;
+
+    // Whether the pull request comes from this forge, judging by how its merge was parsed
+    fn handles(&self, pr: &PullRequestInfo) -> bool;
+
+    // Web page of the pull request, which the notes link to
+    fn pull_request_url(&self, id: u32) -> String;
+
+    // Web page comparing two revisions, e.g. the previous release and this one
+    fn compare_url(&self, from: &str, to: &str) -> String;
+
+    fn fetch_pull_request(&self, id: u32) -> Result<Details, String>;
+
+    // Users who approved the pull request, for --show-reviewers
+    fn fetch_reviewers(&self, id: u32) -> Result<Vec<String>, String>;
+}
+
+#[derive(Clone, Default)]
+pub struct Details {
+    pub title: Option<String>,
+    pub labels: Vec<String>,
+    pub milestone: Option<String>,
+    pub merged_by: Option<String>,
+    pub author_association: Option<String>,
+    // Only fetched when asked for
+    pub reviewers: Option<Vec<String>>
+}
+
+impl Details {
+    fn to_json(&self) -> Value {
+        json!({
+            "title": self.title,
+            "labels": self.labels,
+            "milestone": self.milestone,
+            "merged_by": self.merged_by,
+            "author_association": self.author_association,
+            "reviewers": self.reviewers
+        })
+    }
+
+    fn from_json(json: &Value) -> Details {
+        let string = |key: &str| json.get(key).and_then(|v| v.as_str()).map(|v| v.to_string());
+        Details {
+            title: string("title"),
+            labels: json.get("labels").and_then(|l| l.as_array()).map_or(vec![], |labels| {
+                labels.iter().filter_map(|l| l.as_str()).map(|l| l.to_string()).collect()
+            }),
+            milestone: string("milestone"),
+            merged_by: string("merged_by"),
+            author_association: string("author_association"),
+            reviewers: json.get("reviewers").and_then(|r| r.as_array()).map(|reviewers| {
+                reviewers.iter().filter_map(|r| r.as_str()).map(|r| r.to_string()).collect()
+            })
+        }
+    }
+}
+
+// Users whose latest review of a GitHub or Gitea pull request approved it; comments after an
+// approval leave it standing, requested changes and dismissals withdraw it
+pub fn approvers(reviews: &Value) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for review in reviews.as_array().map_or(&[][..], |r| &r[..]) {
+        let user = match review.pointer("/user/login").and_then(|l| l.as_str()) {
+            Some(user) => user.to_string(),
+            None => continue
+        };
+        match review.get("state").and_then(|s| s.as_str()) {
+            Some("APPROVED") if !result.contains(&user) => result.push(user),
+            Some("CHANGES_REQUESTED") | Some("REQUEST_CHANGES") | Some("DISMISSED") => result.retain(|u| *u != user),
+            _ => {}
+        }
+    }
+    result
+}
+
+// Empty titles are treated as missing, so that they don't replace the one from the merge commit
+pub fn non_empty(s: Option<&str>) -> Option<String> {
+    s.map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| s.to_string())
+}
+
+// The built-in forge for pull requests whose merges were parsed as coming from the given
+// provider, hosted on the remote's host; patch series from SourceHut have no pull requests
+// to fetch
+pub fn for_provider(kind: ::Provider, host: &str, repository: &str) -> Option<Arc<dyn Forge>> {
+    let token = match kind {
+        ::Provider::GitHub => github::token(host, repository).unwrap_or_else(|e| {
+            warn!("Cannot authenticate as a GitHub App: {}", e);
+            None
+        }),
+        _ => auth::token(host)
+    };
+    let forge: Arc<dyn Forge> = match kind {
+        ::Provider::GitHub => Arc::new(GitHub::new(host, repository, token.clone())),
+        ::Provider::GitLab => Arc::new(GitLab::new(host, repository, token.clone())),
+        ::Provider::Gitea => Arc::new(Gitea::new(host, repository, token.clone())),
+        ::Provider::Bitbucket => Arc::new(Bitbucket::new(repository, token.clone())),
+        ::Provider::SourceHut | ::Provider::Auto => return None
+    };
+    if token.is_none() && !replay::replaying() {
+        warn!("No token found for {}, API requests will be rate limited", host);
+    }
+    Some(forge)
+}
+
+// The built-in forges whose pull requests the notes link to, for the repository on the remote's
+// host; the API isn't used for links, so no token is needed. Without a provider, merges may come
+// from any of them
+pub fn for_links(kind: ::Provider, host: &str, repository: &str) -> Vec<Arc<dyn Forge>> {
+    let mut all: Vec<(::Provider, Arc<dyn Forge>)> = vec![
+        (::Provider::GitHub, Arc::new(GitHub::new(host, repository, None))),
+        (::Provider::GitLab, Arc::new(GitLab::new(host, repository, None))),
+        (::Provider::Gitea, Arc::new(Gitea::new(host, repository, None)))
+    ];
+    // Only Bitbucket Cloud is built in
+    if host == "bitbucket.org" {
+        all.push((::Provider::Bitbucket, Arc::new(Bitbucket::new(repository, None))));
+    }
+    all.into_iter().filter(|&(provider, _)| kind == ::Provider::Auto || provider == kind).map(|(_, forge)| forge).collect()
+}
+
+// A GET request, or a POST one when there is a body
+pub fn request(url: &str, headers: &[String], body: Option<&Value>) -> Result<Value, String> {
+    send(if body.is_some() { "POST" } else { "GET" }, url, headers, body)
+}
+
+// An empty response, as to some DELETE requests, is null
+pub fn send(method: &str, url: &str, headers: &[String], body: Option<&Value>) -> Result<Value, String> {
+    if let Some(response) = replay::lookup(method, url) {
+        return response;
+    }
+
+    let response = curl(method, url, headers, body)?;
+    let json: Value = if response.iter().all(|b| b.is_ascii_whitespace()) {
+        Value::Null
+    } else {
+        serde_json::from_slice(&response).map_err(|e| format!("invalid response from {}: {}", url, e))?
+    };
+    replay::record(method, url, &json)?;
+    Ok(json)
+}
+
+// Webhooks answer with plain text like "ok", which is of no interest
+pub fn post(url: &str, headers: &[String], body: &Value) -> Result<(), String> {
+    curl("POST", url, headers, Some(body)).map(|_| ())
+}
+
+// Release assets and other files which aren't JSON
+pub fn download(url: &str, headers: &[String]) -> Result<Vec<u8>, String> {
+    curl("GET", url, headers, None)
+}
+
+// Headers are passed to curl on stdin so that tokens don't show up in the process list,
+// which is why a request body has to go through a temporary file
+fn curl(method: &str, url: &str, headers: &[String], body: Option<&Value>) -> Result<Vec<u8>, String> {
+    let mut cmd = Command::new("curl");
+    cmd.arg("--silent").arg("--show-error").arg("--fail").arg("--location")
+        .arg("--request").arg(method)
+        .arg("--header").arg("@-");
+    let body_path = match body {
+        Some(body) => {
+            let path = env::temp_dir().join(format!("git-pull-requests-{}.json", process::id()));
+            File::create(&path).and_then(|mut f| f.write_all(body.to_string().as_bytes()))
+                .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+            cmd.arg("--header").arg("Content-Type: application/json")
+                .arg("--data-binary").arg(format!("@{}", path.display()));
+            Some(path)
+        }
+        None => None
+    };
+
+    let output = run_curl(cmd.arg(url), headers);
+    if let Some(path) = body_path {
+        let _ = fs::remove_file(path);
+    }
+    let output = output?;
+    if !output.status.success() {
+        return Err(format!("request to {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(output.stdout)
+}
+
+fn run_curl(cmd: &mut Command, headers: &[String]) -> Result<Output, String> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run curl: {}", e))?;
+
+    let headers: String = headers.iter().map(|h| format!("{}\n", h)).collect();
+    child.stdin.take().unwrap().write_all(headers.as_bytes())
+        .map_err(|e| format!("cannot write to curl: {}", e))?;
+
+    child.wait_with_output().map_err(|e| format!("curl failed: {}", e))
+}
+
+// Details fetched by a run which didn't get through all pull requests are kept per repository,
+// so that a run with --resume only has to fetch the remaining ones
+fn progress_path(repository: &str) -> Option<PathBuf> {
+    let name: String = repository.chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+    state_dir().map(|d| d.join("enrich").join(format!("{}.json", name)))
+}
+
+fn load_progress(path: &Path) -> Result<BTreeMap<u32, Details>, String> {
+    let mut text = String::new();
+    match File::open(path) {
+        Ok(mut f) => {
+            f.read_to_string(&mut text).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("cannot open {}: {}", path.display(), e))
+    }
+
+    let json: Value = serde_json::from_str(&text).map_err(|e| format!("invalid progress file {}: {}", path.display(), e))?;
+    let obj = json.as_object().ok_or_else(|| format!("invalid progress file {}", path.display()))?;
+    Ok(obj.iter().filter_map(|(id, details)| id.parse().ok().map(|id| (id, Details::from_json(details)))).collect())
+}
+
+fn save_progress(path: &Path, fetched: &BTreeMap<u32, Details>) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    let obj: serde_json::Map<String, Value> = fetched.iter().map(|(id, details)| (id.to_string(), details.to_json())).collect();
+    let data = serde_json::to_string_pretty(&Value::Object(obj)).unwrap();
+    File::create(path).and_then(|mut f| f.write_all(data.as_bytes()))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+// Failures for individual pull requests only produce warnings, so that e.g. hitting the rate
+// limit halfway through still leaves the rest of the output intact
+pub fn enrich(forge: Arc<dyn Forge>, pull_requests: &mut [PullRequestInfo], concurrency: usize, resume: bool,
+              reviewers: bool) {
+    let progress = progress_path(&forge.repository());
+    let mut fetched = match progress {
+        Some(ref path) if resume => load_progress(path).unwrap_or_else(|e| {
+            warn!("Cannot resume enrichment: {}", e);
+            BTreeMap::new()
+        }),
+        _ => BTreeMap::new()
+    };
+
+    let ids: Vec<u32> = pull_requests.iter()
+        .filter(|pr| forge.handles(pr) && !fetched.contains_key(&pr.id))
+        .map(|pr| pr.id)
+        .collect();
+    if resume {
+        info!("Resuming enrichment, {} pull requests left to fetch", ids.len());
+    }
+
+    let total = ids.len();
+    let fetcher = forge.clone();
+    let results = parallel_chunks(ids, concurrency, move |chunk| {
+        chunk.into_iter()
+            .take_while(|_| cancel::stopped().is_none())
+            .map(|id| {
+                let details = fetcher.fetch_pull_request(id).and_then(|mut details| {
+                    if reviewers {
+                        details.reviewers = Some(fetcher.fetch_reviewers(id)?);
+                    }
+                    Ok(details)
+                });
+                (id, details)
+            })
+            .collect()
+    });
+
+    let mut failed = 0;
+    for (id, result) in results {
+        match result {
+            Ok(details) => { fetched.insert(id, details); }
+            Err(e) => {
+                warn!("Cannot enrich pull request #{}: {}", id, e);
+                failed += 1;
+            }
+        }
+    }
+
+    for pr in pull_requests.iter_mut().filter(|pr| forge.handles(pr)) {
+        if let Some(details) = fetched.get(&pr.id).cloned() {
+            if let Some(title) = details.title {
+                pr.name = title;
+            }
+            pr.labels = details.labels;
+            pr.milestone = details.milestone;
+            pr.merged_by = details.merged_by;
+            pr.author_association = details.author_association;
+            pr.reviewers = details.reviewers;
+        }
+    }
+
+    let path = match progress {
+        Some(path) => path,
+        None => return
+    };
+    let remaining = pull_requests.iter().filter(|pr| forge.handles(pr) && !fetched.contains_key(&pr.id)).count();
+    if remaining == 0 {
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Cannot remove {}: {}", path.display(), e);
+            }
+        }
+    } else {
+        match save_progress(&path, &fetched) {
+            Ok(_) => warn!("{} of {} pull requests were not enriched ({} failed); run again with --resume to fetch only those",
+                           remaining, total, failed),
+            Err(e) => warn!("Cannot save enrichment progress: {}", e)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use forge::{self, Details};
+use {Provider, PullRequestInfo};
+
+// Gitea and Forgejo, which share the API
+pub struct Gitea {
+    host: String,
+    repository: String,
+    token: Option<String>
+}
+
+impl Gitea {
+    pub fn new(host: &str, repository: &str, token: Option<String>) -> Gitea {
+        Gitea { host: host.to_string(), repository: repository.to_string(), token }
+    }
+}
+
+impl forge::Forge for Gitea {
+    fn repository(&self) -> String {
+        format!("{}/{}", self.host, self.repository)
+    }
+
+    fn handles(&self, pr: &PullRequestInfo) -> bool {
+        pr.provider == Provider::Gitea
+    }
+
+    fn pull_request_url(&self, id: u32) -> String {
+        format!("https://{}/{}/pulls/{}", self.host, self.repository, id)
+    }
+
+    fn compare_url(&self, from: &str, to: &str) -> String {
+        format!("https://{}/{}/compare/{}...{}", self.host, self.repository, from, to)
+    }
+
+    fn fetch_pull_request(&self, id: u32) -> Result<Details, String> {
+        let url = format!("https://{}/api/v1/repos/{}/pulls/{}", self.host, self.repository, id);
+        let headers: Vec<String> = self.token.iter().map(|t| format!("Authorization: token {}", t)).collect();
+        let json = forge::request(&url, &headers, None)?;
+
+        Ok(Details {
+            title: forge::non_empty(json.get("title").and_then(|t| t.as_str())),
+            labels: json.get("labels").and_then(|l| l.as_array()).map_or(vec![], |labels| {
+                labels.iter().filter_map(|l| l.get("name").and_then(|n| n.as_str()))
+                    .map(|n| n.to_string()).collect()
+            }),
+            milestone: json.pointer("/milestone/title").and_then(|t| t.as_str()).map(|t| t.to_string()),
+            merged_by: json.pointer("/merged_by/login").and_then(|l| l.as_str()).map(|l| l.to_string()),
+            author_association: None,
+            reviewers: None
+        })
+    }
+
+    fn fetch_reviewers(&self, id: u32) -> Result<Vec<String>, String> {
+        let url = format!("https://{}/api/v1/repos/{}/pulls/{}/reviews", self.host, self.repository, id);
+        let headers: Vec<String> = self.token.iter().map(|t| format!("Authorization: token {}", t)).collect();
+        let json = forge::request(&url, &headers, None)?;
+        Ok(forge::approvers(&json))
+    }
+}
+
+// Gitea and Forgejo put the title into the header, as in `Merge pull request 'Title' (#12) from
+// user/branch into main`, unless the merge message was customized to `Merge pull request '#12'
+// from user/branch`; the user is only there for pull requests from forks
+pub fn parse_header(header: &str) -> Option<Result<(::ParsedHeader, Option<String>), String>> {
+    let titled_pattern = regex!(r"^Merge pull request '(.*)' \(#(\d+)\) from (?:([^/\s]+)/)?(\S+)(?: into \S+)?$");
+    let untitled_pattern = regex!(r"^Merge pull request '#(\d+)' from (?:([^/\s]+)/)?(\S+)(?: into \S+)?$");
+
+    let header = header.trim();
+    let (title, id, author, branch) = if let Some(captures) = titled_pattern.captures(header) {
+        (Some(captures[1].to_string()), captures.get(2), captures.get(3), captures.get(4))
+    } else if let Some(captures) = untitled_pattern.captures(header) {
+        (None, captures.get(1), captures.get(2), captures.get(3))
+    } else {
+        return None;
+    };
+    let id = id.unwrap().as_str();
+    Some(match id.parse() {
+        Ok(id) => Ok(((id, author.map(|m| m.as_str().to_string()), branch.unwrap().as_str().to_string()), title)),
+        Err(e) => Err(format!("invalid pull request id {}: {}", id, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gitea_headers() {
+        assert_eq!(parse_header("Merge pull request 'Fix the parser' (#12) from alice/fix into main"),
+                   Some(Ok(((12, Some("alice".to_string()), "fix".to_string()), Some("Fix the parser".to_string())))));
+        assert_eq!(parse_header("Merge pull request 'Fix (#3) again' (#12) from fix"),
+                   Some(Ok(((12, None, "fix".to_string()), Some("Fix (#3) again".to_string())))));
+        assert_eq!(parse_header("Merge pull request '#12' from alice/fix"),
+                   Some(Ok(((12, Some("alice".to_string()), "fix".to_string()), None))));
+        assert_eq!(parse_header("Merge pull request #12 from alice/fix"), None);
+    }
+}
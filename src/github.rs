@@ -0,0 +1,313 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use auth;
+use forge::{self, Details};
+use {Provider, PullRequestInfo};
+
+// Splits remote URLs like https://github.com/owner/repo.git or git@github.com:owner/repo
+// into the host and the owner/repo slug
+pub fn repository_of_url(url: &str) -> Option<(String, String)> {
+    let (host, path) = match auth::host_of_url(url) {
+        Some(host) => {
+            let rest = &url[url.find(host).unwrap() + host.len()..];
+            let rest = rest.trim_start_matches(|c: char| c == ':' || c.is_ascii_digit());
+            (host, rest)
+        }
+        None => match (url.find('@'), url.find(':')) {
+            (Some(at), Some(colon)) if at < colon => (&url[at + 1..colon], &url[colon + 1..]),
+            _ => return None
+        }
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if path.split('/').count() != 2 {
+        return None;
+    }
+    Some((host.to_string(), path.to_string()))
+}
+
+fn api_root(host: &str) -> String {
+    if host == "github.com" {
+        "https://api.github.com".into()
+    } else {
+        format!("https://{}/api/v3", host)
+    }
+}
+
+fn headers(token: Option<&str>) -> Vec<String> {
+    let mut headers = vec!["Accept: application/vnd.github.v3+json".to_string()];
+    if let Some(token) = token {
+        headers.push(format!("Authorization: token {}", token));
+    }
+    headers
+}
+
+const APP_ID_VAR: &str = "GIT_PULL_REQUESTS_GITHUB_APP_ID";
+const APP_KEY_VAR: &str = "GIT_PULL_REQUESTS_GITHUB_APP_KEY";
+const APP_INSTALLATION_VAR: &str = "GIT_PULL_REQUESTS_GITHUB_APP_INSTALLATION";
+
+fn base64url(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut result = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..chunk.len() + 1 {
+            result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    result
+}
+
+// A JWT identifying the app for ten minutes, signed with its private key by openssl; the
+// issue time is set a minute back in case the local clock is ahead
+fn app_jwt(app_id: &str, key_path: &str) -> Result<String, String> {
+    let now = ::time::get_time().sec;
+    let header = json!({"alg": "RS256", "typ": "JWT"});
+    let claims = json!({"iat": now - 60, "exp": now + 540, "iss": app_id});
+    let unsigned = format!("{}.{}", base64url(header.to_string().as_bytes()), base64url(claims.to_string().as_bytes()));
+
+    let mut child = Command::new("openssl")
+        .arg("dgst").arg("-sha256").arg("-sign").arg(key_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run openssl: {}", e))?;
+    child.stdin.take().unwrap().write_all(unsigned.as_bytes())
+        .map_err(|e| format!("cannot write to openssl: {}", e))?;
+    let output = child.wait_with_output().map_err(|e| format!("openssl failed: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("cannot sign with {}: {}", key_path, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(format!("{}.{}", unsigned, base64url(&output.stdout)))
+}
+
+// Apps act on a repository through their installation on its owner, whose id is looked up
+// unless given; installation tokens are valid for an hour, which is plenty for one run
+fn installation_token(host: &str, repository: &str, app_id: &str, key_path: &str) -> Result<String, String> {
+    let jwt = app_jwt(app_id, key_path)?;
+    let headers = vec!["Accept: application/vnd.github.v3+json".to_string(), format!("Authorization: Bearer {}", jwt)];
+    let installation = match env::var(APP_INSTALLATION_VAR) {
+        Ok(id) => id,
+        Err(_) => {
+            let json = forge::request(&format!("{}/repos/{}/installation", api_root(host), repository), &headers, None)?;
+            json.get("id").and_then(|id| id.as_u64()).map(|id| id.to_string())
+                .ok_or_else(|| format!("GitHub App {} is not installed for {}", app_id, repository))?
+        }
+    };
+    let json = forge::request(&format!("{}/app/installations/{}/access_tokens", api_root(host), installation), &headers,
+                              Some(&json!({})))?;
+    json.get("token").and_then(|t| t.as_str()).map(|t| t.to_string())
+        .ok_or_else(|| format!("no token in the response for installation {}", installation))
+}
+
+// Authenticates as a GitHub App installation when the app id and the path of its private key
+// are set, otherwise uses a personal token
+pub fn token(host: &str, repository: &str) -> Result<Option<String>, String> {
+    match (env::var(APP_ID_VAR), env::var(APP_KEY_VAR)) {
+        (Ok(app_id), Ok(key_path)) => installation_token(host, repository, &app_id, &key_path).map(Some),
+        _ => Ok(auth::token(host))
+    }
+}
+
+fn graphql_root(host: &str) -> String {
+    if host == "github.com" {
+        "https://api.github.com/graphql".into()
+    } else {
+        format!("https://{}/api/graphql", host)
+    }
+}
+
+// Requests which change something on GitHub can't do without a token, unlike reading
+pub fn authenticate(host: &str, repository: &str) -> Result<GitHub, String> {
+    let token = token(host, repository).map_err(|e| format!("cannot authenticate as a GitHub App: {}", e))?
+        .ok_or_else(|| format!("no token found for {}, see `auth login`", host))?;
+    Ok(GitHub::new(host, repository, Some(token)))
+}
+
+// Issues closed by a pull request are referenced as in "Fixes #12" or "closes: #12" in its title
+// or description; references to other repositories like owner/repo#12 are left out
+fn closed_issues(text: &str) -> Vec<u32> {
+    let keyword_pattern = regex!(r"(?i)\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?):?\s+#(\d+)\b");
+    keyword_pattern.captures_iter(text).filter_map(|captures| captures[1].parse().ok()).collect()
+}
+
+pub fn fixed_issues(pull_requests: &[PullRequestInfo]) -> Vec<u32> {
+    let mut issues: Vec<u32> = pull_requests.iter()
+        .flat_map(|pr| closed_issues(&pr.name).into_iter().chain(closed_issues(&pr.body)))
+        .collect();
+    issues.sort();
+    issues.dedup();
+    issues
+}
+
+pub struct GitHub {
+    host: String,
+    repository: String,
+    token: Option<String>
+}
+
+impl GitHub {
+    pub fn new(host: &str, repository: &str, token: Option<String>) -> GitHub {
+        GitHub { host: host.to_string(), repository: repository.to_string(), token }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/repos/{}/{}", api_root(&self.host), self.repository, path)
+    }
+
+    fn headers(&self) -> Vec<String> {
+        headers(self.token.as_ref().map(|t| &t[..]))
+    }
+
+    fn graphql(&self, query: &str, variables: Value) -> Result<Value, String> {
+        let json = forge::request(&graphql_root(&self.host), &self.headers(), Some(&json!({"query": query, "variables": variables})))?;
+        if let Some(errors) = json.get("errors").and_then(|e| e.as_array()).filter(|e| !e.is_empty()) {
+            let messages: Vec<&str> = errors.iter().filter_map(|e| e.get("message").and_then(|m| m.as_str())).collect();
+            return Err(format!("GraphQL request to {} failed: {}", self.host, messages.join("; ")));
+        }
+        Ok(json.get("data").cloned().unwrap_or(Value::Null))
+    }
+
+    // The newest release which is neither a draft nor a prerelease
+    pub fn latest_release(&self) -> Result<Value, String> {
+        forge::request(&self.url("releases/latest"), &self.headers(), None)
+    }
+
+    // Returns the web URL of the created release
+    pub fn create_release(&self, tag: &str, notes: &str) -> Result<String, String> {
+        let body = json!({"tag_name": tag, "name": tag, "body": notes});
+        let json = forge::request(&self.url("releases"), &self.headers(), Some(&body))?;
+        Ok(json.get("html_url").and_then(|u| u.as_str()).unwrap_or("").to_string())
+    }
+
+    // The number of the open milestone with the given title
+    pub fn open_milestone(&self, title: &str) -> Result<u64, String> {
+        let json = forge::request(&self.url("milestones?state=open&per_page=100"), &self.headers(), None)?;
+        json.as_array().map_or(&[][..], |m| &m[..]).iter()
+            .find(|m| m.get("title").and_then(|t| t.as_str()) == Some(title))
+            .and_then(|m| m.get("number")).and_then(|n| n.as_u64())
+            .ok_or_else(|| format!("no open milestone {} in {}", title, self.repository))
+    }
+
+    pub fn close_milestone(&self, number: u64) -> Result<(), String> {
+        forge::send("PATCH", &self.url(&format!("milestones/{}", number)), &self.headers(), Some(&json!({"state": "closed"})))
+            .map(|_| ())
+    }
+
+    // Pull requests are issues as far as comments and labels are concerned
+    pub fn comment(&self, id: u32, text: &str) -> Result<(), String> {
+        forge::request(&self.url(&format!("issues/{}/comments", id)), &self.headers(), Some(&json!({"body": text})))
+            .map(|_| ())
+    }
+
+    pub fn add_label(&self, id: u32, label: &str) -> Result<(), String> {
+        forge::request(&self.url(&format!("issues/{}/labels", id)), &self.headers(), Some(&json!({"labels": [label]})))
+            .map(|_| ())
+    }
+
+    // The ids of the repository and of its discussion category with the given name, which
+    // discussions are created with
+    pub fn discussion_category(&self, name: &str) -> Result<(String, String), String> {
+        let (owner, repo) = self.repository.split_once('/').unwrap_or(("", &self.repository));
+        let data = self.graphql("query($owner: String!, $name: String!) {
+            repository(owner: $owner, name: $name) { id discussionCategories(first: 100) { nodes { id name } } }
+        }", json!({"owner": owner, "name": repo}))?;
+        let repository_id = data.pointer("/repository/id").and_then(|i| i.as_str())
+            .ok_or_else(|| format!("repository {} not found on {}", self.repository, self.host))?;
+        let categories = data.pointer("/repository/discussionCategories/nodes").and_then(|n| n.as_array());
+        categories.map_or(&[][..], |c| &c[..]).iter()
+            .find(|c| c.get("name").and_then(|n| n.as_str()).is_some_and(|n| n.eq_ignore_ascii_case(name)))
+            .and_then(|c| c.get("id")).and_then(|i| i.as_str())
+            .map(|category_id| (repository_id.to_string(), category_id.to_string()))
+            .ok_or_else(|| format!("no discussion category {} in {}, are discussions enabled?", name, self.repository))
+    }
+
+    // Returns the web URL of the created discussion
+    pub fn create_discussion(&self, category: &(String, String), title: &str, body: &str) -> Result<String, String> {
+        let data = self.graphql("mutation($repositoryId: ID!, $categoryId: ID!, $title: String!, $body: String!) {
+            createDiscussion(input: {repositoryId: $repositoryId, categoryId: $categoryId, title: $title, body: $body}) {
+                discussion { url }
+            }
+        }", json!({"repositoryId": category.0, "categoryId": category.1, "title": title, "body": body}))?;
+        Ok(data.pointer("/createDiscussion/discussion/url").and_then(|u| u.as_str()).unwrap_or("").to_string())
+    }
+}
+
+impl forge::Forge for GitHub {
+    fn repository(&self) -> String {
+        format!("{}/{}", self.host, self.repository)
+    }
+
+    fn handles(&self, pr: &PullRequestInfo) -> bool {
+        pr.provider == Provider::GitHub
+    }
+
+    fn pull_request_url(&self, id: u32) -> String {
+        format!("https://{}/{}/pull/{}", self.host, self.repository, id)
+    }
+
+    fn compare_url(&self, from: &str, to: &str) -> String {
+        format!("https://{}/{}/compare/{}...{}", self.host, self.repository, from, to)
+    }
+
+    fn fetch_pull_request(&self, id: u32) -> Result<Details, String> {
+        let json = forge::request(&self.url(&format!("pulls/{}", id)), &self.headers(), None)?;
+
+        Ok(Details {
+            title: forge::non_empty(json.get("title").and_then(|t| t.as_str())),
+            labels: json.get("labels").and_then(|l| l.as_array()).map_or(vec![], |labels| {
+                labels.iter().filter_map(|l| l.get("name").and_then(|n| n.as_str()))
+                    .map(|n| n.to_string()).collect()
+            }),
+            milestone: json.pointer("/milestone/title").and_then(|t| t.as_str()).map(|t| t.to_string()),
+            merged_by: json.pointer("/merged_by/login").and_then(|l| l.as_str()).map(|l| l.to_string()),
+            author_association: json.get("author_association").and_then(|a| a.as_str()).map(|a| a.to_string()),
+            reviewers: None
+        })
+    }
+
+    fn fetch_reviewers(&self, id: u32) -> Result<Vec<String>, String> {
+        let json = forge::request(&self.url(&format!("pulls/{}/reviews?per_page=100", id)), &self.headers(), None)?;
+        Ok(forge::approvers(&json))
+    }
+}
+
+// GitHub names the owner of the branch, as in `Merge pull request #12 from user/branch`, and puts the
+// title into the body
+pub fn parse_header(header: &str) -> Option<Result<(u32, String, String), String>> {
+    let header_pattern = regex!(r"Merge pull request #(\d+) from (.+?)/(.+)");
+    header_pattern.captures(header).map(|captures| {
+        let id = match captures[1].parse() {
+            Ok(id) => id,
+            Err(e) => return Err(format!("invalid pull request id {}: {}", &captures[1], e))
+        };
+        let author = captures[2].to_string();
+        let branch = captures[3].to_string();
+        Ok((id, author, branch))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_github_headers() {
+        assert_eq!(parse_header("Merge pull request #42 from alice/fix/parser"),
+                   Some(Ok((42, "alice".to_string(), "fix/parser".to_string()))));
+        assert!(matches!(parse_header("Merge pull request #99999999999 from alice/x"), Some(Err(_))));
+        assert_eq!(parse_header("Merge branch 'main' into feature"), None);
+    }
+
+    #[test]
+    fn finds_closed_issues() {
+        assert_eq!(closed_issues("Fixes #12, closes: #20\nResolved #3 and fixed #4"), vec![12, 20, 3, 4]);
+        assert_eq!(closed_issues("CLOSE #5"), vec![5]);
+        assert!(closed_issues("See #12, fixes owner/repo#13, prefixes #14, fixes#15").is_empty());
+    }
+}
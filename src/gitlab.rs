@@ -0,0 +1,89 @@
+use forge::{self, Details};
+use {Provider, PullRequestInfo};
+
+pub struct GitLab {
+    host: String,
+    repository: String,
+    token: Option<String>
+}
+
+impl GitLab {
+    pub fn new(host: &str, repository: &str, token: Option<String>) -> GitLab {
+        GitLab { host: host.to_string(), repository: repository.to_string(), token }
+    }
+}
+
+impl forge::Forge for GitLab {
+    fn repository(&self) -> String {
+        format!("{}/{}", self.host, self.repository)
+    }
+
+    fn handles(&self, pr: &PullRequestInfo) -> bool {
+        pr.provider == Provider::GitLab
+    }
+
+    fn pull_request_url(&self, id: u32) -> String {
+        format!("https://{}/{}/-/merge_requests/{}", self.host, self.repository, id)
+    }
+
+    fn compare_url(&self, from: &str, to: &str) -> String {
+        format!("https://{}/{}/-/compare/{}...{}", self.host, self.repository, from, to)
+    }
+
+    // Projects are addressed by their URL-encoded path; labels come as plain strings
+    fn fetch_pull_request(&self, id: u32) -> Result<Details, String> {
+        let url = format!("https://{}/api/v4/projects/{}/merge_requests/{}", self.host,
+                          self.repository.replace('/', "%2F"), id);
+        let headers: Vec<String> = self.token.iter().map(|t| format!("PRIVATE-TOKEN: {}", t)).collect();
+        let json = forge::request(&url, &headers, None)?;
+
+        Ok(Details {
+            title: forge::non_empty(json.get("title").and_then(|t| t.as_str())),
+            labels: json.get("labels").and_then(|l| l.as_array()).map_or(vec![], |labels| {
+                labels.iter().filter_map(|l| l.as_str()).map(|l| l.to_string()).collect()
+            }),
+            milestone: json.pointer("/milestone/title").and_then(|t| t.as_str()).map(|t| t.to_string()),
+            merged_by: json.pointer("/merged_by/username").and_then(|u| u.as_str()).map(|u| u.to_string()),
+            author_association: None,
+            reviewers: None
+        })
+    }
+
+    fn fetch_reviewers(&self, id: u32) -> Result<Vec<String>, String> {
+        let url = format!("https://{}/api/v4/projects/{}/merge_requests/{}/approvals", self.host,
+                          self.repository.replace('/', "%2F"), id);
+        let headers: Vec<String> = self.token.iter().map(|t| format!("PRIVATE-TOKEN: {}", t)).collect();
+        let json = forge::request(&url, &headers, None)?;
+        Ok(json.get("approved_by").and_then(|a| a.as_array()).map_or(vec![], |approvals| {
+            approvals.iter().filter_map(|a| a.pointer("/user/username").and_then(|u| u.as_str()))
+                .map(|u| u.to_string()).collect()
+        }))
+    }
+}
+
+pub fn parse_message(header: &str, body: &str) -> Option<Result<(u32, String), String>> {
+    let header_pattern = regex!(r"^Merge branch '(.+?)' into '.+'$");
+    let reference_pattern = regex!(r"(?m)^See merge request (\S+)!(\d+)\s*$");
+
+    let branch = match header_pattern.captures(header.trim()) {
+        Some(captures) => captures[1].to_string(),
+        None => return None
+    };
+    reference_pattern.captures(body).map(|captures| match captures[2].parse() {
+        Ok(id) => Ok((id, branch)),
+        Err(e) => Err(format!("invalid merge request id {}: {}", &captures[2], e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gitlab_messages() {
+        let body = "Fix the parser\n\nSee merge request group/project!17\n";
+        assert_eq!(parse_message("Merge branch 'fix-parser' into 'main'", body), Some(Ok((17, "fix-parser".to_string()))));
+        assert_eq!(parse_message("Merge branch 'fix-parser' into 'main'", "Fix the parser"), None);
+        assert_eq!(parse_message("Merge branch 'fix-parser' into main", body), None);
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+// Pairs of label or branch prefix and section title; sections are output in the order
+// in which their titles first appear here
+pub type GroupTitles = Vec<(String, String)>;
+
+pub const OTHER: &str = "Other";
+
+pub fn default_titles() -> GroupTitles {
+    [("feature", "Features"), ("feat", "Features"), ("enhancement", "Features"),
+     ("fix", "Bug fixes"), ("bugfix", "Bug fixes"), ("hotfix", "Bug fixes"), ("bug", "Bug fixes"),
+     ("docs", "Documentation"), ("documentation", "Documentation"),
+     ("chore", "Chores")]
+        .iter().map(|&(k, t)| (k.to_string(), t.to_string())).collect()
+}
+
+// Conventional commit types and branch prefixes, see --group-by category
+pub fn category_titles() -> GroupTitles {
+    let mut titles: GroupTitles = vec![("breaking".to_string(), "Breaking changes".to_string())];
+    titles.extend(default_titles());
+    titles.extend([("perf", "Performance"), ("refactor", "Refactoring")]
+        .iter().map(|&(k, t)| (k.to_string(), t.to_string())));
+    titles
+}
+
+pub fn origin_titles() -> GroupTitles {
+    vec![("community".to_string(), "Community contributions".to_string()),
+         ("team".to_string(), "Team changes".to_string())]
+}
+
+// Title files contain `label-or-prefix = title` lines; empty lines and lines starting with # are ignored
+pub fn load(path: &Path) -> Result<GroupTitles, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+    let mut result = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(2, '=').map(|p| p.trim()).collect();
+        if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
+            return Err(format!("{}:{}: expected `label-or-prefix = title`", path.display(), i + 1));
+        }
+        result.push((parts[0].to_lowercase(), parts[1].to_string()));
+    }
+    Ok(result)
+}
+
+pub fn title_of<I: Iterator<Item=String>>(titles: &GroupTitles, keys: I) -> &str {
+    keys.filter_map(|key| titles.iter().find(|&(k, _)| *k == key.to_lowercase()).map(|(_, t)| &t[..]))
+        .next()
+        .unwrap_or(OTHER)
+}
+
+pub fn ordered_titles(titles: &GroupTitles) -> Vec<&str> {
+    let mut result: Vec<&str> = Vec::new();
+    for (_, title) in titles {
+        if !result.contains(&&title[..]) {
+            result.push(title);
+        }
+    }
+    if !result.contains(&OTHER) {
+        result.push(OTHER);
+    }
+    result
+}
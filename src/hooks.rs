@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use serde_json::{Map, Value};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stage {
+    PreGenerate,
+    PostGenerate,
+    PrePublish,
+    PostPublish
+}
+
+pub const STAGES: [Stage; 4] = [Stage::PreGenerate, Stage::PostGenerate, Stage::PrePublish, Stage::PostPublish];
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::PreGenerate => "pre-generate",
+            Stage::PostGenerate => "post-generate",
+            Stage::PrePublish => "pre-publish",
+            Stage::PostPublish => "post-publish"
+        }
+    }
+}
+
+// Hook commands by stage name, and the payload they get as JSON on stdin; the payload grows
+// as the run goes on, e.g. the notes are only there from post-generate on
+pub struct Hooks {
+    commands: BTreeMap<String, String>,
+    payload: Map<String, Value>
+}
+
+impl Hooks {
+    pub fn new(commands: BTreeMap<String, String>) -> Hooks {
+        Hooks {
+            commands,
+            payload: Map::new()
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.payload.insert(key.to_string(), value);
+    }
+
+    // A failing hook stops the run, so pre- hooks can veto generating or publishing
+    pub fn run(&self, stage: Stage) -> Result<(), String> {
+        let cmd = match self.commands.get(stage.name()) {
+            Some(cmd) => cmd,
+            None => return Ok(())
+        };
+        info!("Running {} hook", stage.name());
+
+        let mut payload = self.payload.clone();
+        payload.insert("stage".to_string(), json!(stage.name()));
+
+        // What hooks print goes to stderr, so that it doesn't end up in the notes on stdout
+        let mut child = Command::new("sh")
+            .arg("-c").arg(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::from(io::stderr()))
+            .spawn()
+            .map_err(|e| format!("cannot run {} hook: {}", stage.name(), e))?;
+        // Hooks which don't need the payload may exit without reading it
+        match child.stdin.take().unwrap().write_all(Value::Object(payload).to_string().as_bytes()) {
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(e) => return Err(format!("cannot write to {} hook: {}", stage.name(), e)),
+            Ok(_) => {}
+        }
+
+        let status = child.wait().map_err(|e| format!("{} hook failed: {}", stage.name(), e))?;
+        if !status.success() {
+            return Err(format!("{} hook exited with {}", stage.name(), status));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,3483 @@
+extern crate git2;
+extern crate regex;
+extern crate itertools;
+#[macro_use] extern crate log;
+extern crate time;
+extern crate unicode_width;
+extern crate handlebars;
+#[macro_use] extern crate serde_json;
+extern crate toml;
+extern crate ctrlc;
+
+use std::cmp;
+use std::fmt;
+use std::str::FromStr;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::thread;
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+use itertools::Itertools;
+use git2::Repository;
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
+
+use error::Error;
+
+// Compiles the regex on first use and keeps it for the rest of the run
+macro_rules! regex {
+    ($re:expr) => {{
+        static REGEX: ::std::sync::OnceLock<::regex::Regex> = ::std::sync::OnceLock::new();
+        REGEX.get_or_init(|| ::regex::Regex::new($re).unwrap())
+    }}
+}
+
+pub mod auth;
+pub mod backport;
+pub mod bitbucket;
+pub mod cache;
+pub mod cancel;
+pub mod changelog;
+pub mod error;
+pub mod flags;
+pub mod forge;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+pub mod groups;
+pub mod hooks;
+pub mod notify;
+pub mod orgs;
+pub mod paths;
+pub mod release;
+pub mod replay;
+pub mod report;
+pub mod settings;
+pub mod stats;
+pub mod template;
+pub mod update;
+
+// Optional titles with the pull requests under them
+pub type Sections = Vec<(Option<String>, Vec<PullRequestInfo>)>;
+
+// Pull request id, author if known and branch
+type ParsedHeader = (u32, Option<String>, String);
+type ParsedMessage = (Provider, u32, Option<String>, String);
+
+pub const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
+
+#[derive(Clone)]
+pub struct Config {
+    pub output_format: OutputFormat,
+    pub provider: Provider,
+    pub header_patterns: Vec<Regex>,
+    pub link_base: Option<String>,
+    // Forges linked to for the pull requests they handle, before falling back to link_base
+    pub forges: Vec<Arc<dyn forge::Forge>>,
+    pub group_by: GroupBy,
+    pub repo_name: Option<String>,
+    pub omit_author: bool,
+    pub empty_title_policy: EmptyTitlePolicy,
+    pub humanize_branch: bool,
+    pub branch_prefixes: Vec<String>,
+    pub title_from: TitleSource,
+    pub octopus: OctopusPolicy,
+    pub include_squash: bool,
+    pub include_plain_merges: bool,
+    pub include_direct_commits: bool,
+    pub include_other_changes: bool,
+    pub first_parent: bool,
+    pub patchsets: bool,
+    pub since: Option<Date>,
+    pub until: Option<Date>,
+    pub show_commits: bool,
+    pub show_first_release: bool,
+    pub show_reviewers: bool,
+    // Set when merge dates are shown
+    pub date_format: Option<String>,
+    pub show_sha: Option<ShaStyle>,
+    pub show_email: bool,
+    pub quiet: bool,
+    pub normalize: bool,
+    pub heading_level: usize,
+    pub section_style: SectionStyle,
+    pub release_name: Option<String>,
+    pub announce_url: Option<String>,
+    pub max_post_length: usize,
+    pub translate_cmd: Option<String>,
+    // Threads used for parsing commits and computing diffs
+    pub jobs: usize
+}
+
+// Matches the defaults of the command line options
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            output_format: OutputFormat::Markdown,
+            provider: Provider::Auto,
+            header_patterns: Vec::new(),
+            link_base: None,
+            forges: Vec::new(),
+            group_by: GroupBy::Nothing,
+            repo_name: None,
+            omit_author: false,
+            empty_title_policy: EmptyTitlePolicy::Branch,
+            humanize_branch: false,
+            branch_prefixes: Vec::new(),
+            title_from: TitleSource::Body,
+            octopus: OctopusPolicy::Warn,
+            include_squash: false,
+            include_plain_merges: false,
+            include_direct_commits: false,
+            include_other_changes: false,
+            first_parent: false,
+            patchsets: false,
+            since: None,
+            until: None,
+            show_commits: false,
+            show_first_release: false,
+            show_reviewers: false,
+            date_format: None,
+            show_sha: None,
+            show_email: false,
+            quiet: false,
+            normalize: false,
+            heading_level: 3,
+            section_style: SectionStyle::Heading,
+            release_name: None,
+            announce_url: None,
+            max_post_length: 500,
+            translate_cmd: None,
+            jobs: 1
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum OutputFormat {
+    Markdown,
+    CherryPickScript,
+    Microblog,
+    Summary,
+    Table,
+    Json
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "markdown" => Ok(OutputFormat::Markdown),
+            "cherry-pick-script" => Ok(OutputFormat::CherryPickScript),
+            "microblog" => Ok(OutputFormat::Microblog),
+            "summary" => Ok(OutputFormat::Summary),
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            s => Err(format!("unknown format: {}", s))
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn format(self, info: &PullRequestInfo, config: &Config) -> String {
+        match self {
+            OutputFormat::Markdown => {
+                let mut r: String = " * ".into();
+                if info.author_check_failed {
+                    r.push_str("[!] ");
+                }
+                let reference = format!("{}{}", config.repo_name.as_ref().map_or("", |r| &r[..]), info.reference());
+                match config.pull_request_url(info) {
+                    Some(url) => write!(&mut r, "[{}]({}) ", reference, url).unwrap(),
+                    None => write!(&mut r, "{} ", reference).unwrap()
+                }
+                if let Some(sha) = config.merge_sha(&info.sha) {
+                    write!(&mut r, "{} ", sha).unwrap();
+                }
+                if !config.omit_author {
+                    write!(&mut r, "(by {}) ", info.author).unwrap();
+                }
+                write!(&mut r, "- {}", info.name).unwrap();
+                if let Some(ref format) = config.date_format {
+                    write!(&mut r, " (merged {})", config.date(info.time, format)).unwrap();
+                }
+                if let Some(ref release) = info.first_release {
+                    write!(&mut r, " (released in {})", release).unwrap();
+                }
+                match info.reviewers {
+                    Some(ref reviewers) if config.show_reviewers && !reviewers.is_empty() =>
+                        write!(&mut r, " (approved by {})", reviewers.join(", ")).unwrap(),
+                    _ => {}
+                }
+                if let Some(ref reverted_by) = info.reverted_by {
+                    write!(&mut r, " (reverted in {})", reverted_by).unwrap();
+                }
+                if let Some(ref excerpt) = info.excerpt {
+                    write!(&mut r, "\n\n    > {}\n", excerpt).unwrap();
+                }
+                for commit in &info.commits {
+                    write!(&mut r, "\n    * {} ({})", commit.summary, config.short_sha(&commit.sha)).unwrap();
+                }
+                r
+            }
+            // Patch series are picked as a range of plain commits
+            OutputFormat::CherryPickScript => match (info.provider, info.commits.first()) {
+                (Provider::SourceHut, Some(first)) =>
+                    format!("# {} - {}\ngit cherry-pick {}^..{}", info.reference(), info.name.lines().next().unwrap_or(""),
+                            config.sha(&first.sha), config.sha(&info.sha)),
+                _ => format!("# {} - {}\ngit cherry-pick -m 1 {}", info.reference(),
+                             info.name.lines().next().unwrap_or(""), config.sha(&info.sha))
+            },
+            _ => format!("- {}", info.name.lines().next().unwrap_or(""))
+        }
+    }
+
+    pub fn format_commit(self, info: &CommitInfo, config: &Config) -> String {
+        match self {
+            OutputFormat::Markdown => {
+                let mut r: String = " * ".into();
+                if !config.omit_author {
+                    write!(&mut r, "(by {}) ", info.author).unwrap();
+                }
+                write!(&mut r, "- {}", info.summary).unwrap();
+                r
+            }
+            OutputFormat::CherryPickScript => {
+                let mainline = if info.is_merge { "-m 1 " } else { "" };
+                format!("# {}\ngit cherry-pick {}{}", info.summary, mainline, config.sha(&info.sha))
+            }
+            _ => format!("- {}", info.summary)
+        }
+    }
+
+    pub fn format_change(self, info: &CommitInfo, config: &Config) -> String {
+        match self {
+            OutputFormat::CherryPickScript => format!("# {}\ngit cherry-pick {}", info.summary, config.sha(&info.sha)),
+            _ => self.format_item(&format!("{} {}", config.short_sha(&info.sha), info.summary))
+        }
+    }
+
+    pub fn format_item(self, text: &str) -> String {
+        match self {
+            OutputFormat::Markdown => format!(" * {}", text),
+            OutputFormat::CherryPickScript => format!("# {}", text),
+            _ => format!("- {}", text)
+        }
+    }
+
+    pub fn format_section_header(self, title: &str, config: &Config) -> String {
+        match self {
+            OutputFormat::Markdown => match config.section_style {
+                SectionStyle::Heading => format!("\n{} {}\n", "#".repeat(config.heading_level), title),
+                SectionStyle::Bold => format!("\n**{}**\n", title)
+            },
+            OutputFormat::CherryPickScript => format!("\n# {}", title),
+            _ => format!("\n{}:", title)
+        }
+    }
+
+    pub fn format_header(self) -> Option<String> {
+        match self {
+            OutputFormat::CherryPickScript => Some("#!/bin/sh\nset -e".into()),
+            _ => None
+        }
+    }
+
+    // Cherry-picks have to be applied in the order the changes were merged
+    pub fn oldest_first(self) -> bool {
+        matches!(self, OutputFormat::CherryPickScript)
+    }
+
+    // Formats which describe the whole set of pull requests at once instead of listing them
+    pub fn format_document(self, pull_requests: &[PullRequestInfo], summary: &Summary, config: &Config) -> Option<String> {
+        match self {
+            OutputFormat::Microblog => Some(microblog_posts(pull_requests, config).join("\n\n---\n\n")),
+            OutputFormat::Summary => Some(summary_paragraph(pull_requests, config)),
+            OutputFormat::Table => Some(table(pull_requests, config)),
+            OutputFormat::Json => {
+                let mut json = json!({"summary": summary.to_json(), "pull_requests": pull_requests_json(pull_requests)});
+                if config.normalize {
+                    normalize_json(&mut json);
+                }
+                Some(serde_json::to_string_pretty(&json).unwrap())
+            }
+            _ => None
+        }
+    }
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        s.into()
+    } else {
+        let mut r: String = s.chars().take(max.saturating_sub(1)).collect();
+        r.push('…');
+        r
+    }
+}
+
+fn pad(s: &str, width: usize) -> String {
+    let mut r: String = s.into();
+    for _ in UnicodeWidthStr::width(s)..width {
+        r.push(' ');
+    }
+    r
+}
+
+// Column widths are computed in terminal cells, so wide (e.g. CJK) and combining characters align properly
+fn table(pull_requests: &[PullRequestInfo], config: &Config) -> String {
+    let mut header = vec!["PR".to_string()];
+    if config.show_sha.is_some() {
+        header.push("SHA".into());
+    }
+    if config.date_format.is_some() {
+        header.push("Merged".into());
+    }
+    if !config.omit_author {
+        header.push("Author".into());
+    }
+    header.push("Title".into());
+
+    let rows: Vec<Vec<String>> = pull_requests.iter().map(|pr| {
+        let mut row = vec![format!("{}{}", config.repo_name.as_ref().map_or("", |r| &r[..]), pr.reference())];
+        if let Some(sha) = config.merge_sha(&pr.sha) {
+            row.push(sha.to_string());
+        }
+        if let Some(ref format) = config.date_format {
+            row.push(config.date(pr.time, format).replace("|", "\\|"));
+        }
+        if !config.omit_author {
+            row.push(pr.author.replace("|", "\\|"));
+        }
+        row.push(pr.name.lines().next().unwrap_or("").replace("|", "\\|"));
+        row
+    }).collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|h| UnicodeWidthStr::width(&h[..])).collect();
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = cmp::max(widths[i], UnicodeWidthStr::width(&cell[..]));
+        }
+    }
+
+    let format_row = |row: &[String]| format!("| {} |", row.iter().zip(widths.iter()).map(|(c, &w)| pad(c, w)).join(" | "));
+    let mut lines = vec![format_row(&header)];
+    lines.push(format!("|{}|", widths.iter().map(|&w| "-".repeat(w + 2)).join("|")));
+    for row in &rows {
+        lines.push(format_row(row));
+    }
+    lines.join("\n")
+}
+
+fn join_names(names: &[String]) -> String {
+    match names.len() {
+        0 => String::new(),
+        1 => names[0].clone(),
+        n => format!("{} and {}", names[..n - 1].join(", "), names[n - 1])
+    }
+}
+
+// Category keys with the singular and plural nouns used when counting pull requests, in the
+// order they are mentioned
+const CATEGORY_NOUNS: [(&[&str], &str, &str); 7] = [
+    (&["breaking"], "breaking change", "breaking changes"),
+    (&["feat", "feature", "enhancement"], "feature", "features"),
+    (&["fix", "bugfix", "hotfix", "bug"], "bug fix", "bug fixes"),
+    (&["perf"], "performance improvement", "performance improvements"),
+    (&["refactor"], "refactoring", "refactorings"),
+    (&["docs", "documentation"], "documentation change", "documentation changes"),
+    (&["chore"], "chore", "chores")
+];
+
+fn counted(n: usize, singular: &str, plural: &str) -> String {
+    format!("{} {}", n, if n == 1 { singular } else { plural })
+}
+
+// Counts pull requests by category, like "2 features, 3 bug fixes and 1 other pull request"
+fn category_counts(pull_requests: &[PullRequestInfo]) -> String {
+    let mut counts = [0; CATEGORY_NOUNS.len()];
+    let mut other = 0;
+    for pr in pull_requests {
+        let key = category_key(pr);
+        match CATEGORY_NOUNS.iter().position(|&(keys, _, _)| key.as_ref().is_some_and(|k| keys.contains(&&k[..]))) {
+            Some(i) => counts[i] += 1,
+            None => other += 1
+        }
+    }
+    if other == pull_requests.len() {
+        return counted(other, "pull request", "pull requests");
+    }
+
+    let mut parts: Vec<String> = CATEGORY_NOUNS.iter().zip(counts.iter())
+        .filter(|&(_, &n)| n > 0)
+        .map(|(&(_, singular, plural), &n)| counted(n, singular, plural))
+        .collect();
+    if other > 0 {
+        parts.push(counted(other, "other pull request", "other pull requests"));
+    }
+    join_names(&parts)
+}
+
+fn summary_paragraph(pull_requests: &[PullRequestInfo], config: &Config) -> String {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for pr in pull_requests {
+        *counts.entry(&pr.author[..]).or_insert(0) += 1;
+    }
+    let mut authors: Vec<(&str, usize)> = counts.into_iter().collect();
+    authors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let mut r = String::new();
+    match config.release_name {
+        Some(ref release) => write!(&mut r, "Release {}", release).unwrap(),
+        None => r.push_str("This release")
+    }
+    if pull_requests.is_empty() {
+        r.push_str(" contains no merged pull requests.");
+        return r;
+    }
+
+    write!(&mut r, " contains {}", category_counts(pull_requests)).unwrap();
+    if !config.omit_author {
+        let people = if authors.len() == 1 { "1 person".to_string() } else { format!("{} people", authors.len()) };
+        write!(&mut r, " contributed by {}", people).unwrap();
+        let top: Vec<String> = authors.iter().take(3).map(|&(a, _)| a.to_string()).collect();
+        if authors.len() > top.len() {
+            write!(&mut r, ", including {}", join_names(&top)).unwrap();
+        } else {
+            write!(&mut r, ": {}", join_names(&top)).unwrap();
+        }
+    }
+    r.push('.');
+    r
+}
+
+fn microblog_posts(pull_requests: &[PullRequestInfo], config: &Config) -> Vec<String> {
+    // leaves room for " (10/10)" thread numbering
+    let limit = config.max_post_length.saturating_sub(8);
+
+    let mut intro = String::new();
+    if let Some(ref repo) = config.repo_name {
+        write!(&mut intro, "{} ", repo).unwrap();
+    }
+    if let Some(ref release) = config.release_name {
+        write!(&mut intro, "{} ", release).unwrap();
+    }
+    if pull_requests.is_empty() {
+        intro.push_str("is out.");
+    } else {
+        write!(&mut intro, "is out: {}.", category_counts(pull_requests)).unwrap();
+    }
+    if let Some(ref url) = config.announce_url {
+        write!(&mut intro, " {}", url).unwrap();
+    }
+    if !pull_requests.is_empty() {
+        intro.push_str("\nHighlights:");
+    }
+
+    let mut posts = Vec::new();
+    let mut current = truncate_chars(&intro, limit);
+    for pr in pull_requests {
+        let mut item = format!("- {}", pr.name.lines().next().unwrap_or(""));
+        if let Some(sha) = config.merge_sha(&pr.sha) {
+            write!(&mut item, " ({})", sha).unwrap();
+        }
+        let item = truncate_chars(&item, limit);
+        if current.chars().count() + 1 + item.chars().count() > limit {
+            posts.push(current);
+            current = item;
+        } else {
+            current.push('\n');
+            current.push_str(&item);
+        }
+    }
+    posts.push(current);
+
+    let n = posts.len();
+    if n > 1 {
+        for (i, post) in posts.iter_mut().enumerate() {
+            write!(post, " ({}/{})", i + 1, n).unwrap();
+        }
+    }
+    posts
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Provider {
+    GitHub,
+    GitLab,
+    Gitea,
+    Bitbucket,
+    // Patch series applied from lists.sr.ht, see --patchsets
+    SourceHut,
+    Auto
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Provider, String> {
+        match s {
+            "github" => Ok(Provider::GitHub),
+            "gitlab" => Ok(Provider::GitLab),
+            "gitea" | "forgejo" => Ok(Provider::Gitea),
+            "bitbucket" => Ok(Provider::Bitbucket),
+            "sourcehut" => Ok(Provider::SourceHut),
+            "auto" => Ok(Provider::Auto),
+            s => Err(format!("unknown provider: {}", s))
+        }
+    }
+}
+
+impl Provider {
+    fn name(self) -> &'static str {
+        match self {
+            Provider::GitHub => "github",
+            Provider::GitLab => "gitlab",
+            Provider::Gitea => "gitea",
+            Provider::Bitbucket => "bitbucket",
+            Provider::SourceHut => "sourcehut",
+            Provider::Auto => "auto"
+        }
+    }
+
+    fn id_prefix(self) -> &'static str {
+        match self {
+            Provider::GitLab => "!",
+            _ => "#"
+        }
+    }
+
+    fn link(self, base: &str, id: u32) -> String {
+        match self {
+            Provider::GitLab => format!("{}/-/merge_requests/{}", base, id),
+            Provider::Gitea => format!("{}/pulls/{}", base, id),
+            Provider::Bitbucket => format!("{}/pull-requests/{}", base, id),
+            _ => format!("{}/pull/{}", base, id)
+        }
+    }
+
+    // Web page comparing two revisions
+    fn compare_link(self, base: &str, from: &str, to: &str) -> String {
+        match self {
+            Provider::GitLab => format!("{}/-/compare/{}...{}", base, from, to),
+            Provider::Bitbucket => format!("{}/branches/compare/{}%0D{}", base, to, from),
+            _ => format!("{}/compare/{}...{}", base, from, to)
+        }
+    }
+
+    fn accepts(self, other: Provider) -> bool {
+        self == Provider::Auto || self == other
+    }
+}
+
+// How markdown section titles are set off, for embedding the output into other documents
+#[derive(Copy, Clone)]
+pub enum SectionStyle {
+    Heading,
+    Bold
+}
+
+impl FromStr for SectionStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SectionStyle, String> {
+        match s {
+            "heading" => Ok(SectionStyle::Heading),
+            "bold" => Ok(SectionStyle::Bold),
+            s => Err(format!("unknown section style: {}", s))
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GroupBy {
+    Label,
+    BranchPrefix,
+    Origin,
+    Category,
+    Nothing
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GroupBy, String> {
+        match s {
+            "label" => Ok(GroupBy::Label),
+            "branch-prefix" => Ok(GroupBy::BranchPrefix),
+            "origin" => Ok(GroupBy::Origin),
+            "category" => Ok(GroupBy::Category),
+            "none" => Ok(GroupBy::Nothing),
+            s => Err(format!("unknown grouping: {}", s))
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum SortKey {
+    PrNumber,
+    Date,
+    Author
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortKey, String> {
+        match s {
+            "pr-number" => Ok(SortKey::PrNumber),
+            "date" => Ok(SortKey::Date),
+            "author" => Ok(SortKey::Author),
+            s => Err(format!("unknown sort key: {}", s))
+        }
+    }
+}
+
+impl GroupBy {
+    fn keys(self, info: &PullRequestInfo) -> Vec<String> {
+        match self {
+            GroupBy::Label => info.labels.clone(),
+            GroupBy::BranchPrefix => match info.branch.find('/') {
+                Some(i) => vec![info.branch[..i].to_string()],
+                None => vec![]
+            },
+            // Owners and members of the organization make up the team, everybody else, including
+            // outside collaborators with access to the repository, is the community
+            GroupBy::Origin => match info.author_association.as_ref().map(|a| &a[..]) {
+                Some("OWNER") | Some("MEMBER") => vec!["team".to_string()],
+                Some(_) => vec!["community".to_string()],
+                None => vec![]
+            },
+            GroupBy::Category => category_key(info).into_iter().collect(),
+            GroupBy::Nothing => vec![]
+        }
+    }
+}
+
+// The lowercased type of a conventional commit style title like "feat(parser): ..." and
+// whether it is marked as breaking by a ! before the colon, as in "fix!: ..."
+fn parse_conventional_title(title: &str) -> Option<(String, bool)> {
+    let type_pattern = regex!(r"^([A-Za-z]+)(?:\([^)]*\))?(!?):\s");
+    type_pattern.captures(title).map(|captures| (captures[1].to_lowercase(), !captures[2].is_empty()))
+}
+
+// The type of a conventional commit style title, or breaking for one marked with a !,
+// otherwise the branch prefix like feat in feat/parser
+fn category_key(info: &PullRequestInfo) -> Option<String> {
+    match parse_conventional_title(&info.name) {
+        Some((_, true)) => Some("breaking".to_string()),
+        Some((kind, false)) => Some(kind),
+        None => info.branch.find('/').map(|i| info.branch[..i].to_lowercase())
+    }
+}
+
+// Labels are compared case-insensitively, like the backport marker
+pub fn without_labels(pull_requests: Vec<PullRequestInfo>, labels: &[String]) -> Vec<PullRequestInfo> {
+    let labels: Vec<String> = labels.iter().map(|l| l.to_lowercase()).collect();
+    pull_requests.into_iter().filter(|pr| !pr.labels.iter().any(|l| labels.contains(&l.to_lowercase()))).collect()
+}
+
+// Pull requests whose type or branch prefix isn't in the table are left without a category
+pub fn categorize(pull_requests: &mut [PullRequestInfo], titles: &groups::GroupTitles) {
+    for pr in pull_requests {
+        pr.category = category_key(pr).and_then(|key| titles.iter().find(|&(k, _)| *k == key).map(|(_, t)| t.clone()));
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum EmptyTitlePolicy {
+    Branch,
+    // Fetch the title from the forge, see fetch_empty_titles
+    Api,
+    Skip,
+    Blank
+}
+
+impl FromStr for EmptyTitlePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EmptyTitlePolicy, String> {
+        match s {
+            "branch" => Ok(EmptyTitlePolicy::Branch),
+            "api" => Ok(EmptyTitlePolicy::Api),
+            "skip" => Ok(EmptyTitlePolicy::Skip),
+            "blank" => Ok(EmptyTitlePolicy::Blank),
+            s => Err(format!("unknown empty title policy: {}", s))
+        }
+    }
+}
+
+impl EmptyTitlePolicy {
+    fn apply(self, mut info: PullRequestInfo, config: &Config) -> Option<PullRequestInfo> {
+        if !info.name.is_empty() {
+            return Some(info);
+        }
+        match self {
+            EmptyTitlePolicy::Branch => {
+                info.name = branch_title(&info.branch, config);
+                Some(info)
+            }
+            EmptyTitlePolicy::Skip => {
+                info!("Skipping pull request #{} with empty title", info.id);
+                None
+            }
+            EmptyTitlePolicy::Api | EmptyTitlePolicy::Blank => Some(info)
+        }
+    }
+}
+
+// Titles which are still empty after enrichment are fetched one by one; when that fails the
+// title is made from the branch, as with the branch policy
+fn fetch_empty_titles(pull_requests: &mut [PullRequestInfo], host: &str, repository: &str, config: &Config) {
+    // Forges ask for a token when created, so there is one per provider rather than per pull request
+    let mut forges: Vec<(Provider, Option<Arc<dyn forge::Forge>>)> = Vec::new();
+    for pr in pull_requests.iter().filter(|pr| pr.name.is_empty()) {
+        if !forges.iter().any(|&(provider, _)| provider == pr.provider) {
+            forges.push((pr.provider, forge::for_provider(pr.provider, host, repository)));
+        }
+    }
+
+    for pr in pull_requests.iter_mut().filter(|pr| pr.name.is_empty()) {
+        let forge = forges.iter().find(|&&(provider, _)| provider == pr.provider).and_then(|(_, forge)| forge.as_ref());
+        let title = match forge {
+            Some(forge) => match forge.fetch_pull_request(pr.id) {
+                Ok(details) => details.title.filter(|t| !t.is_empty()),
+                Err(e) => {
+                    warn!("Cannot fetch the title of pull request #{}: {}", pr.id, e);
+                    None
+                }
+            },
+            None => None
+        };
+        pr.name = title.unwrap_or_else(|| branch_title(&pr.branch, config));
+    }
+}
+
+fn branch_title(branch: &str, config: &Config) -> String {
+    let mut branch = branch;
+    for prefix in &config.branch_prefixes {
+        if branch.starts_with(&prefix[..]) && branch.len() > prefix.len() {
+            branch = &branch[prefix.len()..];
+            break;
+        }
+    }
+
+    if !config.humanize_branch {
+        return branch.replace("-", " ");
+    }
+
+    let words = branch.split(['/', '-', '_']).filter(|w| !w.is_empty()).join(" ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => words
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RevertPolicy {
+    Drop,
+    Annotate,
+    Ignore
+}
+
+impl FromStr for RevertPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RevertPolicy, String> {
+        match s {
+            "drop" => Ok(RevertPolicy::Drop),
+            "annotate" => Ok(RevertPolicy::Annotate),
+            "ignore" => Ok(RevertPolicy::Ignore),
+            s => Err(format!("unknown revert policy: {}", s))
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum ShaStyle {
+    Short,
+    Full
+}
+
+impl FromStr for ShaStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ShaStyle, String> {
+        match s {
+            "short" => Ok(ShaStyle::Short),
+            "full" => Ok(ShaStyle::Full),
+            s => Err(format!("unknown SHA style: {}", s))
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum TitleSource {
+    Body,
+    FirstLine,
+    FirstParagraph
+}
+
+impl FromStr for TitleSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TitleSource, String> {
+        match s {
+            "body" => Ok(TitleSource::Body),
+            "first-line" => Ok(TitleSource::FirstLine),
+            "first-paragraph" => Ok(TitleSource::FirstParagraph),
+            s => Err(format!("unknown title source: {}", s))
+        }
+    }
+}
+
+impl TitleSource {
+    fn extract(self, body: &str) -> String {
+        match self {
+            TitleSource::Body => body.into(),
+            TitleSource::FirstLine =>
+                body.lines().map(|l| l.trim()).find(|l| !l.is_empty()).unwrap_or("").into(),
+            TitleSource::FirstParagraph =>
+                body.lines()
+                    .map(|l| l.trim())
+                    .skip_while(|l| l.is_empty())
+                    .take_while(|l| !l.is_empty())
+                    .join(" ")
+        }
+    }
+}
+
+// Removes pull request template boilerplate: HTML comments, checklists and section headers
+fn clean_body(body: &str) -> String {
+    let comment_pattern = regex!(r"(?s)<!--.*?-->");
+    let checkbox_pattern = regex!(r"^\s*[-*] \[[ xX]\]");
+    let header_pattern = regex!(r"^\s*#{1,6}\s");
+
+    let body = comment_pattern.replace_all(body, "");
+    let mut result = String::new();
+    let mut blank = true;
+    for line in body.lines().filter(|l| !checkbox_pattern.is_match(l) && !header_pattern.is_match(l)) {
+        if line.trim().is_empty() {
+            if !blank {
+                result.push('\n');
+            }
+            blank = true;
+        } else {
+            result.push_str(line.trim_end());
+            result.push('\n');
+            blank = false;
+        }
+    }
+    result.trim().into()
+}
+
+#[derive(Copy, Clone)]
+pub enum OctopusPolicy {
+    Expand,
+    Warn,
+    Skip
+}
+
+impl FromStr for OctopusPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OctopusPolicy, String> {
+        match s {
+            "expand" => Ok(OctopusPolicy::Expand),
+            "warn" => Ok(OctopusPolicy::Warn),
+            "skip" => Ok(OctopusPolicy::Skip),
+            s => Err(format!("unknown octopus policy: {}", s))
+        }
+    }
+}
+
+impl OctopusPolicy {
+    fn apply<'a>(self, c: git2::Commit<'a>, config: &Config) -> Option<Result<Vec<PullRequestInfo>, ParseError>> {
+        match self {
+            OctopusPolicy::Expand => Some(PullRequestInfo::from_octopus_commit(c, config)),
+            OctopusPolicy::Warn => {
+                if !config.quiet {
+                    warn!("Skipping octopus merge commit {} with {} parents", c.id(), c.parents().len());
+                }
+                None
+            }
+            OctopusPolicy::Skip => None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PullRequestInfo {
+    pub provider: Provider,
+    pub id: u32,
+    pub sha: String,
+    pub time: i64,
+    pub author: String,
+    pub branch: String,
+    pub name: String,
+    pub body: String,
+    pub commits: Vec<CommitInfo>,
+    pub first_release: Option<String>,
+    pub author_check_failed: bool,
+    pub excerpt: Option<String>,
+    pub labels: Vec<String>,
+    pub milestone: Option<String>,
+    pub merged_by: Option<String>,
+    pub author_association: Option<String>,
+    // Users who approved it, when fetched for --show-reviewers
+    pub reviewers: Option<Vec<String>>,
+    pub email: Option<String>,
+    // The pull request, or the SHA of the commit, which reverted this one in the same range
+    pub reverted_by: Option<String>,
+    // Web URL when it doesn't follow from the link base and the id, as for patch series
+    pub url: Option<String>,
+    // Section title from the conventional commit type of the title or the branch prefix
+    pub category: Option<String>
+}
+
+#[derive(Clone, Debug)]
+pub struct CommitInfo {
+    pub sha: String,
+    pub author: String,
+    pub summary: String,
+    pub is_merge: bool
+}
+
+impl CommitInfo {
+    fn from_commit<'a>(c: &git2::Commit<'a>) -> CommitInfo {
+        CommitInfo {
+            sha: c.id().to_string(),
+            author: c.author().name().unwrap_or("").into(),
+            summary: c.message().and_then(|m| m.lines().next()).unwrap_or("").trim().into(),
+            is_merge: c.parents().len() > 1
+        }
+    }
+}
+
+// Follows first parents from the tip of the range, collecting non-merge commits,
+// that is, commits which were made directly on the target branch
+fn direct_commits(repo: &Repository, tip: git2::Oid, in_range: &HashSet<git2::Oid>, config: &Config) -> Vec<CommitInfo> {
+    let mut result = Vec::new();
+    let mut current = Some(tip);
+    while let Some(oid) = current {
+        if !in_range.contains(&oid) {
+            break;
+        }
+        let c = match repo.find_commit(oid) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("cannot find commit {}: {}", oid, e);
+                break;
+            }
+        };
+        if c.parents().len() == 1 && !(config.include_squash && is_squash(&c)) && config.in_period(&c) {
+            result.push(CommitInfo::from_commit(&c));
+        }
+        current = c.parent_ids().next();
+    }
+    result
+}
+
+// Non-merge commits in the range which none of the pull request merges brought in, e.g. commits
+// pushed to the branch or merged without a pull request; the commits of a merge are those
+// reachable from its other parents but not from the first one
+fn other_changes(repo: &Repository, oids: &[git2::Oid], pr_commits: &[git2::Oid],
+                 config: &Config) -> Result<Vec<CommitInfo>, String> {
+    let mut covered: HashSet<git2::Oid> = pr_commits.iter().cloned().collect();
+    for &oid in pr_commits {
+        let c = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+        if c.parents().len() < 2 {
+            continue;
+        }
+        let mut revwalk = repo.revwalk().map_err(|e| format!("cannot get revwalk: {}", e))?;
+        for parent in c.parent_ids().skip(1) {
+            revwalk.push(parent).map_err(|e| format!("cannot walk from {}: {}", parent, e))?;
+        }
+        revwalk.hide(c.parent_id(0).unwrap()).map_err(|e| format!("cannot walk from {}: {}", oid, e))?;
+        for merged in revwalk {
+            covered.insert(merged.map_err(|e| format!("cannot walk history: {}", e))?);
+        }
+    }
+
+    let mut result = Vec::new();
+    for &oid in oids {
+        if covered.contains(&oid) {
+            continue;
+        }
+        let c = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+        if c.parents().len() < 2 && config.in_period(&c) {
+            result.push(CommitInfo::from_commit(&c));
+        }
+    }
+    Ok(result)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let value = *[prev[j] + cost, prev[j + 1] + 1, cur[j] + 1].iter().min().unwrap();
+            cur.push(value);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn suggest_tags(repo: &Repository, name: &str) -> Vec<String> {
+    let tags = match repo.tag_names(None) {
+        Ok(tags) => tags,
+        Err(_) => return vec![]
+    };
+    let mut candidates: Vec<(usize, String)> = tags.iter()
+        .flatten()
+        .map(|t| (edit_distance(name, t), t.into()))
+        .filter(|&(d, _)| d <= 3)
+        .collect();
+    candidates.sort();
+    candidates.into_iter().take(3).map(|(_, t)| t).collect()
+}
+
+pub fn validate_range(repo: &Repository, range: &str) -> Result<(), String> {
+    let parts: Vec<&str> = range.splitn(2, "..").collect();
+    if parts.len() == 2 && parts[1].starts_with(".") {
+        return Err(format!("invalid range {}, expected <from>..<to>", range));
+    }
+
+    for endpoint in parts.iter().filter(|p| !p.is_empty()) {
+        if repo.revparse_single(endpoint).is_err() {
+            let suggestions = suggest_tags(repo, endpoint);
+            return Err(if suggestions.is_empty() {
+                format!("revision {} not found", endpoint)
+            } else {
+                format!("revision {} not found; did you mean {}?", endpoint, suggestions.join(" or "))
+            });
+        }
+    }
+    Ok(())
+}
+
+// A range without ".." is a single revision, meaning all of its history
+fn push_range(revwalk: &mut git2::Revwalk, repo: &Repository, range: &str) -> Result<(), git2::Error> {
+    if range.contains("..") {
+        revwalk.push_range(range)
+    } else {
+        let obj = repo.revparse_single(&format!("{}^{{commit}}", range))?;
+        revwalk.push(obj.id())
+    }
+}
+
+fn range_tip(repo: &Repository, range: &str) -> Result<git2::Oid, String> {
+    let spec = repo.revparse(range).map_err(|e| format!("cannot parse range {}: {}", range, e))?;
+    match spec.to().or(spec.from()) {
+        Some(tip) => Ok(tip.id()),
+        None => Err(format!("range {} has no end commit", range))
+    }
+}
+
+fn tags_by_commit(repo: &Repository) -> HashMap<git2::Oid, Vec<String>> {
+    let mut result = HashMap::new();
+    let tags = match repo.tag_names(None) {
+        Ok(tags) => tags,
+        Err(e) => {
+            warn!("cannot list tags: {}", e);
+            return result;
+        }
+    };
+    for tag in tags.iter().flatten() {
+        match repo.revparse_single(&format!("refs/tags/{}^{{commit}}", tag)) {
+            Ok(obj) => result.entry(obj.id()).or_insert(vec![]).push(tag.into()),
+            Err(e) => warn!("cannot resolve tag {}: {}", tag, e)
+        }
+    }
+    result
+}
+
+// Returns tags reachable from the given commit, most recent first
+fn tagged_ancestors(repo: &Repository, start: git2::Oid) -> Result<Vec<String>, git2::Error> {
+    let tags = tags_by_commit(repo);
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(start)?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+    let mut result = Vec::new();
+    for oid in revwalk {
+        if let Some(names) = tags.get(&oid?) {
+            result.extend(names.iter().cloned());
+        }
+    }
+    Ok(result)
+}
+
+pub fn release_range(repo: &Repository, tag: &str) -> Result<String, String> {
+    let obj = repo.revparse_single(&format!("{}^{{commit}}", tag)).map_err(|e| {
+        let suggestions = suggest_tags(repo, tag);
+        if suggestions.is_empty() {
+            format!("tag {} not found: {}", tag, e)
+        } else {
+            format!("tag {} not found; did you mean {}?", tag, suggestions.join(" or "))
+        }
+    })?;
+    let c = repo.find_commit(obj.id()).map_err(|e| format!("cannot find commit for tag {}: {}", tag, e))?;
+    let previous = match c.parent_ids().next() {
+        Some(parent) => tagged_ancestors(repo, parent).map_err(|e| format!("cannot walk history: {}", e))?.into_iter().next(),
+        None => None
+    };
+    Ok(match previous {
+        Some(previous) => format!("{}..{}", previous, tag),
+        None => tag.into()
+    })
+}
+
+pub fn last_releases_range(repo: &Repository, n: usize) -> Result<String, String> {
+    if n == 0 {
+        return Err("number of releases must be positive".into());
+    }
+    let head = repo.revparse_single("HEAD").map_err(|e| format!("cannot resolve HEAD: {}", e))?;
+    let tags = tagged_ancestors(repo, head.id()).map_err(|e| format!("cannot walk history: {}", e))?;
+    if tags.is_empty() {
+        return Err("no tags found in the history of HEAD".into());
+    }
+    Ok(match tags.get(n) {
+        Some(start) => format!("{}..{}", start, tags[0]),
+        None => tags[0].clone()
+    })
+}
+
+pub fn latest_tag(repo: &Repository, rev: &str) -> Result<String, String> {
+    let obj = repo.revparse_single(&format!("{}^{{commit}}", rev)).map_err(|e| format!("cannot resolve {}: {}", rev, e))?;
+    let tags = tagged_ancestors(repo, obj.id()).map_err(|e| format!("cannot walk history: {}", e))?;
+    tags.into_iter().next().ok_or_else(|| format!("no tags found in the history of {}", rev))
+}
+
+pub fn since_last_tag_range(repo: &Repository, to: &str) -> Result<String, String> {
+    latest_tag(repo, to).map(|tag| format!("{}..{}", tag, to))
+}
+
+fn remote_callbacks<'a>() -> git2::RemoteCallbacks<'a> {
+    let mut tried_agent = false;
+    let mut tried_token = false;
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(git2::CredentialType::SSH_KEY) && !tried_agent {
+            tried_agent = true;
+            return git2::Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) && !tried_token {
+            tried_token = true;
+            if let Some(token) = auth::host_of_url(url).and_then(auth::token) {
+                return git2::Cred::userpass_plaintext("x-access-token", &token);
+            }
+        }
+        Err(git2::Error::from_str(&format!("no usable credentials for {}", url)))
+    });
+    callbacks
+}
+
+pub fn fetch_remote(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(name)?;
+    let mut options = git2::FetchOptions::new();
+    options.remote_callbacks(remote_callbacks());
+
+    info!("Fetching remote {}", name);
+    remote.fetch::<&str>(&[], Some(&mut options), None)
+}
+
+// Mirrors are bare repositories with all branches and tags fetched into local refs,
+// so ranges can be specified with plain branch names
+pub fn open_mirror(url: &str) -> Result<Repository, String> {
+    let path = cache::mirror_path(url).ok_or_else(|| "cannot determine cache directory".to_string())?;
+    let repo = if path.exists() {
+        info!("Using cached mirror {}", path.display());
+        Repository::open(&path).map_err(|e| format!("cannot open mirror {}: {}", path.display(), e))?
+    } else {
+        info!("Creating mirror of {} in {}", url, path.display());
+        fs::create_dir_all(&path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+        let repo = Repository::init_bare(&path).map_err(|e| format!("cannot create mirror {}: {}", path.display(), e))?;
+        repo.remote("origin", url).map_err(|e| format!("cannot add remote {}: {}", url, e))?;
+        repo
+    };
+
+    {
+        let mut remote = repo.find_remote("origin").map_err(|e| format!("cannot find mirror remote: {}", e))?;
+        let mut options = git2::FetchOptions::new();
+        options.remote_callbacks(remote_callbacks());
+        remote.fetch(&["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"], Some(&mut options), None)
+            .map_err(|e| format!("cannot fetch {}: {}", url, e))?;
+
+        // A new bare repository has HEAD pointing to master, while ranges like those of
+        // --since-last-tag start from HEAD, so it follows the default branch of the remote
+        let branch = match remote.default_branch() {
+            Ok(branch) => branch,
+            Err(_) => remote.connect_auth(git2::Direction::Fetch, Some(remote_callbacks()), None)
+                .and_then(|connection| connection.default_branch())
+                .map_err(|e| format!("cannot get the default branch of {}: {}", url, e))?
+        };
+        if let Some(branch) = branch.as_str() {
+            repo.set_head(branch).map_err(|e| format!("cannot set HEAD of mirror to {}: {}", branch, e))?;
+        }
+    }
+    Ok(repo)
+}
+
+// Commits brought in by a merge are those reachable from its second parent but not from the first one
+fn merged_commits<'a>(repo: &Repository, c: &git2::Commit<'a>) -> Result<Vec<CommitInfo>, git2::Error> {
+    let parents: Vec<git2::Oid> = c.parent_ids().collect();
+    if parents.len() < 2 {
+        return Ok(vec![]);
+    }
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(parents[1])?;
+    revwalk.hide(parents[0])?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+
+    let mut result = Vec::new();
+    for oid in revwalk {
+        result.push(CommitInfo::from_commit(&repo.find_commit(oid?)?));
+    }
+    Ok(result)
+}
+
+// Fills in the commits of pull requests collected without listing them, for outputs of
+// --all-profiles which list them; a single patch is its own commit, and octopus merges have none
+pub fn list_commits(repo: &Repository, pull_requests: &mut [PullRequestInfo]) -> Result<(), git2::Error> {
+    for pr in pull_requests.iter_mut().filter(|pr| pr.commits.is_empty()) {
+        let c = repo.find_commit(git2::Oid::from_str(&pr.sha)?)?;
+        pr.commits = match pr.provider {
+            Provider::SourceHut => vec![CommitInfo::from_commit(&c)],
+            _ if c.parent_count() > 2 => continue,
+            _ => merged_commits(repo, &c)?
+        };
+    }
+    Ok(())
+}
+
+fn is_ancestor(repo: &Repository, ancestor: git2::Oid, descendant: git2::Oid) -> Result<bool, git2::Error> {
+    if ancestor == descendant {
+        return Ok(true);
+    }
+    match repo.merge_base(ancestor, descendant) {
+        Ok(base) => Ok(base == ancestor),
+        Err(ref e) if e.code() == git2::ErrorCode::NotFound => Ok(false),
+        Err(e) => Err(e)
+    }
+}
+
+// Targets are either revisions or <path>:<line>, in which case the commit which last touched the line is used
+fn resolve_target(repo: &Repository, target: &str) -> Result<git2::Oid, String> {
+    if let Ok(obj) = repo.revparse_single(&format!("{}^{{commit}}", target)) {
+        return Ok(obj.id());
+    }
+
+    let parts: Vec<&str> = target.rsplitn(2, ':').collect();
+    if parts.len() == 2 {
+        if let Ok(line) = parts[0].parse::<usize>() {
+            let path = Path::new(parts[1]);
+            let blame = repo.blame_file(path, None).map_err(|e| format!("cannot blame {}: {}", path.display(), e))?;
+            return match blame.get_line(line) {
+                Some(hunk) => Ok(hunk.final_commit_id()),
+                None => Err(format!("line {} not found in {}", line, path.display()))
+            };
+        }
+    }
+    Err(format!("{} is neither a revision nor <path>:<line>", target))
+}
+
+// How a commit came into the first-parent history of HEAD
+pub enum Introduction {
+    // The pull request and its merge commit
+    PullRequest(Box<PullRequestInfo>, git2::Oid),
+    Direct(git2::Oid)
+}
+
+// Walks the first-parent history of HEAD looking for the commit which brought the target into it
+pub fn which_pr(repo: &Repository, target: &str, config: &Config) -> Result<Introduction, String> {
+    let target = resolve_target(repo, target)?;
+    let head = repo.revparse_single("HEAD").map_err(|e| format!("cannot resolve HEAD: {}", e))?.id();
+    if !is_ancestor(repo, target, head).map_err(|e| format!("cannot check ancestry: {}", e))? {
+        return Err(format!("commit {} is not in the history of HEAD", target));
+    }
+
+    let mut current = head;
+    loop {
+        let c = repo.find_commit(current).map_err(|e| format!("cannot find commit {}: {}", current, e))?;
+        let first_parent = c.parent_ids().next();
+        let in_parent = match first_parent {
+            Some(parent) => is_ancestor(repo, target, parent).map_err(|e| format!("cannot check ancestry: {}", e))?,
+            None => false
+        };
+        if !in_parent {
+            let merge_id = c.id();
+            return Ok(match pull_request_of(repo, c, config)? {
+                Some(pr) => Introduction::PullRequest(Box::new(pr), merge_id),
+                None => Introduction::Direct(target)
+            });
+        }
+        current = first_parent.unwrap();
+    }
+}
+
+// A day or a second in UTC, as the half-open interval of Unix timestamps it covers, so that
+// --until 2026-09-30 includes the whole day
+#[derive(Copy, Clone, Debug)]
+pub struct Date {
+    start: i64,
+    end: i64
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Date, String> {
+        // strptime ignores trailing input, so the shape is checked first
+        let (format, length) = if regex!(r"^\d{4}-\d{2}-\d{2}$").is_match(s) {
+            ("%Y-%m-%d", 86400)
+        } else if regex!(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}$").is_match(s) {
+            ("%Y-%m-%dT%H:%M:%S", 1)
+        } else {
+            return Err(format!("invalid date {}, expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS", s));
+        };
+        let tm = time::strptime(s, format).map_err(|e| format!("invalid date {}: {}", s, e))?;
+        let start = tm.to_timespec().sec;
+        Ok(Date { start, end: start + length })
+    }
+}
+
+impl Config {
+    // Whether the commit was committed between --since and --until
+    fn in_period<'a>(&self, c: &git2::Commit<'a>) -> bool {
+        let seconds = c.time().seconds();
+        self.since.is_none_or(|d| seconds >= d.start) && self.until.is_none_or(|d| seconds < d.end)
+    }
+
+    // JSON output always maps pull requests to their commits, for tools tracing commits back
+    // to pull requests
+    pub fn lists_commits(&self) -> bool {
+        self.show_commits || matches!(self.output_format, OutputFormat::Json)
+    }
+
+    // With --normalize, SHAs and dates, which change whenever history is rewritten or notes
+    // are regenerated, are replaced with placeholders so that the output can be snapshot-tested
+    fn sha<'s>(&self, sha: &'s str) -> &'s str {
+        if self.normalize { "<sha>" } else { sha }
+    }
+
+    fn short_sha<'s>(&self, sha: &'s str) -> &'s str {
+        if self.normalize { "<sha>" } else { &sha[..7] }
+    }
+
+    // The merge commit SHA shown with --show-sha
+    pub fn merge_sha<'s>(&self, sha: &'s str) -> Option<&'s str> {
+        self.show_sha.map(|style| match style {
+            ShaStyle::Short => self.short_sha(sha),
+            ShaStyle::Full => self.sha(sha)
+        })
+    }
+
+    fn date(&self, seconds: i64, format: &str) -> String {
+        if self.normalize { "<date>".into() } else { format_time_utc(seconds, format) }
+    }
+
+    fn pull_request_url(&self, pr: &PullRequestInfo) -> Option<String> {
+        pr.url.clone()
+            .or_else(|| self.forges.iter().find(|f| f.handles(pr)).map(|f| f.pull_request_url(pr.id)))
+            .or_else(|| self.link_base.as_ref().map(|base| pr.provider.link(base, pr.id)))
+    }
+
+    // Web page comparing two revisions on the forge the pull requests come from
+    pub fn compare_url(&self, pull_requests: &[PullRequestInfo], from: &str, to: &str) -> Option<String> {
+        let forge = self.forges.iter().find(|f| pull_requests.first().is_none_or(|pr| f.handles(pr)));
+        if let Some(forge) = forge {
+            return Some(forge.compare_url(from, to));
+        }
+        // Without --provider the kind of forge is known from the merges
+        let provider = match self.provider {
+            Provider::Auto => pull_requests.first().map_or(Provider::GitHub, |pr| pr.provider),
+            provider => provider
+        };
+        self.link_base.as_ref().map(|base| provider.compare_link(base, from, to))
+    }
+}
+
+// The JSON counterpart of the Config placeholders, for JSON and template output
+pub fn normalize_json(json: &mut Value) {
+    match *json {
+        Value::Object(ref mut obj) => for (key, value) in obj.iter_mut() {
+            match &key[..] {
+                "sha" | "short_sha" => *value = json!("<sha>"),
+                "date" => *value = json!("<date>"),
+                _ => normalize_json(value)
+            }
+        },
+        Value::Array(ref mut items) => for item in items {
+            normalize_json(item);
+        },
+        _ => {}
+    }
+}
+
+pub fn format_time(seconds: i64, format: &str) -> String {
+    format_tm(time::at(time::Timespec::new(seconds, 0)), format)
+}
+
+pub fn format_time_utc(seconds: i64, format: &str) -> String {
+    format_tm(time::at_utc(time::Timespec::new(seconds, 0)), format)
+}
+
+pub fn format_tm(tm: time::Tm, format: &str) -> String {
+    match tm.strftime(format) {
+        Ok(s) => s.to_string(),
+        Err(e) => {
+            warn!("invalid date format {}: {}", format, e);
+            String::new()
+        }
+    }
+}
+
+fn containing_tags(repo: &Repository, oid: git2::Oid) -> Result<Vec<String>, git2::Error> {
+    let mut result = Vec::new();
+    for (tagged, names) in tags_by_commit(repo) {
+        if is_ancestor(repo, oid, tagged)? {
+            result.extend(names);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+// Positions of the commits in the order they were made, ancestors before their descendants;
+// only parents among the given commits count, which otherwise keep their oldest first order
+pub fn merge_order(repo: &Repository, oids: &[git2::Oid]) -> Result<HashMap<git2::Oid, usize>, git2::Error> {
+    let in_range: HashSet<git2::Oid> = oids.iter().cloned().collect();
+    let mut order = HashMap::new();
+    let mut stack = Vec::new();
+    for &oid in oids.iter().rev() {
+        stack.push((oid, false));
+        while let Some((oid, visited)) = stack.pop() {
+            if order.contains_key(&oid) {
+                continue;
+            }
+            if visited {
+                let position = order.len();
+                order.insert(oid, position);
+                continue;
+            }
+            stack.push((oid, true));
+            let parents: Vec<git2::Oid> = repo.find_commit(oid)?.parent_ids().collect();
+            for parent in parents.into_iter().rev() {
+                if in_range.contains(&parent) && !order.contains_key(&parent) {
+                    stack.push((parent, false));
+                }
+            }
+        }
+    }
+    Ok(order)
+}
+
+// Tags ordered by the time of their commits, oldest first
+pub fn tags_by_time(repo: &Repository) -> Vec<(String, git2::Oid)> {
+    let mut tags: Vec<(i64, String, git2::Oid)> = Vec::new();
+    for (oid, names) in tags_by_commit(repo) {
+        let time = match repo.find_commit(oid) {
+            Ok(c) => c.time().seconds(),
+            Err(_) => continue
+        };
+        for name in names {
+            tags.push((time, name, oid));
+        }
+    }
+    tags.sort();
+    tags.into_iter().map(|(_, name, oid)| (name, oid)).collect()
+}
+
+pub fn first_release(repo: &Repository, tags: &[(String, git2::Oid)], oid: git2::Oid) -> Result<Option<String>, git2::Error> {
+    for &(ref name, tagged) in tags {
+        if is_ancestor(repo, oid, tagged)? {
+            return Ok(Some(name.clone()));
+        }
+    }
+    Ok(None)
+}
+
+// Assigns every pull request to the earliest tag within the range which contains it;
+// sections are returned newest first, with untagged pull requests first of all
+pub fn split_by_tag(repo: &Repository, in_range: &HashSet<git2::Oid>, pull_requests: Vec<PullRequestInfo>, config: &Config)
+    -> Result<Vec<(String, Vec<PullRequestInfo>)>, git2::Error>
+{
+    let tags: Vec<(String, git2::Oid)> = tags_by_time(repo).into_iter()
+        .filter(|&(_, oid)| in_range.contains(&oid))
+        .collect();
+
+    let mut unreleased = Vec::new();
+    let mut sections: Vec<(String, Vec<PullRequestInfo>)> = tags.iter().map(|&(ref name, oid)| {
+        let date = repo.find_commit(oid).map(|c| config.date(c.time().seconds(), "%Y-%m-%d")).unwrap_or_default();
+        (format!("{} ({})", name, date), Vec::new())
+    }).collect();
+
+    for pr in pull_requests {
+        let oid = git2::Oid::from_str(&pr.sha).unwrap();
+        match first_release(repo, &tags, oid)? {
+            Some(tag) => {
+                let idx = tags.iter().position(|(name, _)| *name == tag).unwrap();
+                sections[idx].1.push(pr);
+            }
+            None => unreleased.push(pr)
+        }
+    }
+
+    sections.reverse();
+    if !unreleased.is_empty() {
+        sections.insert(0, ("Unreleased".into(), unreleased));
+    }
+    Ok(sections.into_iter().filter(|(_, prs)| !prs.is_empty()).collect())
+}
+
+// Assigns every pull request to the first of the walked ranges which contains its commit,
+// keeping the ranges in the given order
+pub fn split_by_range(walks: &[(String, Vec<git2::Oid>)], pull_requests: Vec<PullRequestInfo>) -> Vec<(String, Vec<PullRequestInfo>)> {
+    let sets: Vec<HashSet<git2::Oid>> = walks.iter().map(|(_, oids)| oids.iter().cloned().collect()).collect();
+    let mut sections: Vec<(String, Vec<PullRequestInfo>)> = walks.iter().map(|(range, _)| (range.clone(), Vec::new())).collect();
+    for pr in pull_requests {
+        let oid = git2::Oid::from_str(&pr.sha).unwrap();
+        if let Some(idx) = sets.iter().position(|set| set.contains(&oid)) {
+            sections[idx].1.push(pr);
+        }
+    }
+    sections.into_iter().filter(|(_, prs)| !prs.is_empty()).collect()
+}
+
+// Unlike the main walk, this also accepts non-merge commits with pull request headers,
+// which is what cherry-picking a merge with -m 1 produces
+fn pull_requests_in_range(repo: &Repository, range: &str, config: &Config) -> Result<Vec<PullRequestInfo>, String> {
+    let mut revwalk = repo.revwalk().map_err(|e| format!("cannot get revwalk: {}", e))?;
+    push_range(&mut revwalk, repo, range).map_err(|e| format!("error pushing range {}: {}", range, e))?;
+    revwalk.set_sorting(git2::Sort::TIME).map_err(|e| format!("cannot sort revwalk: {}", e))?;
+
+    // Patches of a series are listed once, as the newest one comes first
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for oid in revwalk {
+        let oid = oid.map_err(|e| format!("cannot walk history: {}", e))?;
+        let c = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+        let series = match pull_request_kind(&c, config) {
+            Some(PullRequestKind::Patch) => c.message().and_then(parse_patch_link).map(|(key, _, _)| key),
+            Some(_) => None,
+            None => continue
+        };
+        match pull_request_of(repo, c, config) {
+            Ok(Some(pr)) => if seen.insert(series.unwrap_or_else(|| pr.reference())) {
+                result.push(pr);
+            },
+            Ok(None) => {}
+            Err(e) => if !config.quiet {
+                warn!("Error parsing commit: {}", e);
+            }
+        }
+    }
+    Ok(result)
+}
+
+pub fn divergence(repo: &Repository, a: &str, b: &str, config: &Config) -> Result<Vec<(String, Vec<PullRequestInfo>)>, String> {
+    let a_id = repo.revparse_single(&format!("{}^{{commit}}", a)).map_err(|e| format!("cannot resolve {}: {}", a, e))?.id();
+    let b_id = repo.revparse_single(&format!("{}^{{commit}}", b)).map_err(|e| format!("cannot resolve {}: {}", b, e))?.id();
+    let base = repo.merge_base(a_id, b_id).map_err(|e| format!("{} and {} have no common history: {}", a, b, e))?;
+
+    let in_a = pull_requests_in_range(repo, &format!("{}..{}", base, a_id), config)?;
+    let in_b = pull_requests_in_range(repo, &format!("{}..{}", base, b_id), config)?;
+    let a_ids: HashSet<String> = in_a.iter().map(|pr| pr.reference()).collect();
+    let b_ids: HashSet<String> = in_b.iter().map(|pr| pr.reference()).collect();
+
+    let (in_both, only_in_a): (Vec<_>, Vec<_>) = in_a.into_iter().partition(|pr| b_ids.contains(&pr.reference()));
+    let only_in_b = in_b.into_iter().filter(|pr| !a_ids.contains(&pr.reference())).collect();
+    Ok(vec![
+        (format!("Only in {}", a), only_in_a),
+        (format!("Only in {}", b), only_in_b),
+        (format!("In both {} and {}", a, b), in_both)
+    ])
+}
+
+// What a commit message says it reverts: `Revert "<title>"` lines, where the title may also be
+// that of a merge commit, GitHub's `Reverts owner/repo#N` and git's `This reverts commit <sha>`;
+// pull requests are newest first, so an id or title refers to the latest merge before the revert
+// when a pull request was merged again after being reverted
+fn revert_targets(message: &str, time: i64, pull_requests: &[PullRequestInfo]) -> Vec<usize> {
+    let title_pattern = regex!(r#"^Revert "(.+)"(?: \(#\d+\))?$"#);
+    let id_pattern = regex!(r"^Reverts (?:[\w.-]+/[\w.-]+)?#(\d+)");
+    let sha_pattern = regex!(r"This reverts commit ([0-9a-f]{40})");
+
+    let mut targets = Vec::new();
+    for line in message.lines().map(|l| l.trim()) {
+        if let Some(captures) = title_pattern.captures(line) {
+            let title = &captures[1];
+            match github::parse_header(title) {
+                Some(Ok((id, _, _))) =>
+                    targets.extend(pull_requests.iter().position(|pr| pr.id == id && pr.time <= time)),
+                _ => targets.extend(pull_requests.iter()
+                    .position(|pr| pr.name.lines().next() == Some(title) && pr.time <= time))
+            }
+        } else if let Some(captures) = id_pattern.captures(line) {
+            if let Ok(id) = captures[1].parse::<u32>() {
+                targets.extend(pull_requests.iter().position(|pr| pr.id == id && pr.time <= time));
+            }
+        }
+        for captures in sha_pattern.captures_iter(line) {
+            targets.extend(pull_requests.iter().position(|pr| pr.sha == captures[1]));
+        }
+    }
+    targets
+}
+
+// Pull requests reverted by a commit or pull request in the same range are dropped, together
+// with the reverting pull requests, or annotated with what reverted them
+pub fn handle_reverts(repo: &Repository, oids: &[git2::Oid], mut pull_requests: Vec<PullRequestInfo>,
+                      policy: RevertPolicy, config: &Config) -> Result<Vec<PullRequestInfo>, String> {
+    if policy == RevertPolicy::Ignore {
+        return Ok(pull_requests);
+    }
+
+    // Pairs of reverted and reverting pull request, the latter missing for plain revert commits
+    let mut reverts: Vec<(usize, Option<usize>, git2::Oid)> = Vec::new();
+    for &oid in oids {
+        let c = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+        let message = match c.message() {
+            Some(message) if message.contains("Revert") || message.contains("revert") => message,
+            _ => continue
+        };
+        let reverting = pull_requests.iter().position(|pr| pr.sha == oid.to_string());
+        for target in revert_targets(message, c.time().seconds(), &pull_requests) {
+            if Some(target) != reverting {
+                reverts.push((target, reverting, oid));
+            }
+        }
+    }
+
+    match policy {
+        RevertPolicy::Drop => {
+            let dropped: HashSet<usize> = reverts.iter()
+                .flat_map(|&(target, reverting, _)| Some(target).into_iter().chain(reverting))
+                .collect();
+            let mut index = 0;
+            pull_requests.retain(|_| {
+                index += 1;
+                !dropped.contains(&(index - 1))
+            });
+        }
+        // The revert commit of a revert pull request reverts the same one, the pull request is
+        // what should be shown
+        _ => for (target, reverting, oid) in reverts {
+            match reverting {
+                Some(i) => {
+                    let by = pull_requests[i].reference();
+                    pull_requests[target].reverted_by = Some(by);
+                }
+                None if pull_requests[target].reverted_by.is_none() =>
+                    pull_requests[target].reverted_by = Some(config.short_sha(&oid.to_string()).to_string()),
+                None => {}
+            }
+        }
+    }
+    Ok(pull_requests)
+}
+
+// A pull request merged more than once, e.g. merged again after a revert or through several merge
+// paths, is only listed for its latest merge; returns the duplicated references as well
+pub fn dedup_pull_requests(pull_requests: Vec<PullRequestInfo>) -> (Vec<PullRequestInfo>, Vec<String>) {
+    let mut latest: HashMap<(Provider, String), usize> = HashMap::new();
+    for (i, pr) in pull_requests.iter().enumerate() {
+        let best = latest.entry((pr.provider, pr.reference())).or_insert(i);
+        if pr.time > pull_requests[*best].time {
+            *best = i;
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    let mut result = Vec::new();
+    for (i, pr) in pull_requests.into_iter().enumerate() {
+        if latest[&(pr.provider, pr.reference())] == i {
+            result.push(pr);
+        } else {
+            duplicates.push(pr.reference());
+        }
+    }
+    duplicates.sort();
+    duplicates.dedup();
+    (result, duplicates)
+}
+
+// Pull requests are marked by a label named like the marker, or by the marker in their title or
+// description when they have no labels; a pull request is on the target branch if its merge
+// commit is reachable from it, or if a commit referencing the same pull request id (e.g.
+// a cherry-picked merge) exists there
+pub fn backport_candidates(repo: &Repository, branch: &str, marker: &str, pull_requests: Vec<PullRequestInfo>, config: &Config)
+    -> Result<Vec<PullRequestInfo>, String>
+{
+    let target = repo.revparse_single(&format!("{}^{{commit}}", branch)).map_err(|e| format!("cannot resolve {}: {}", branch, e))?.id();
+    let head = repo.revparse_single("HEAD").map_err(|e| format!("cannot resolve HEAD: {}", e))?.id();
+    let base = repo.merge_base(head, target).map_err(|e| format!("{} and HEAD have no common history: {}", branch, e))?;
+    let on_target: HashSet<String> = pull_requests_in_range(repo, &format!("{}..{}", base, target), config)?
+        .into_iter().map(|pr| pr.reference()).collect();
+
+    let marker = marker.to_lowercase();
+    let mut result = Vec::new();
+    for pr in pull_requests {
+        // Without labels, e.g. when not enriched, the marker may still be in the text
+        let marked = if pr.labels.is_empty() {
+            pr.name.to_lowercase().contains(&marker) || pr.body.to_lowercase().contains(&marker)
+        } else {
+            pr.labels.iter().any(|l| l.to_lowercase() == marker)
+        };
+        if !marked || on_target.contains(&pr.reference()) {
+            continue;
+        }
+        let oid = git2::Oid::from_str(&pr.sha).unwrap();
+        if !is_ancestor(repo, oid, target).map_err(|e| format!("cannot check ancestry: {}", e))? {
+            result.push(pr);
+        }
+    }
+    Ok(result)
+}
+
+// Only git knows how to check signatures against the configured keyrings and signing
+// formats, so verification is left to git verify-commit
+fn is_compliant_commit(repo: &Repository, c: &git2::Commit) -> Result<bool, String> {
+    let signed_off = c.message().is_some_and(|m| m.lines().any(|l| l.starts_with("Signed-off-by:")));
+    if !signed_off {
+        return Ok(false);
+    }
+    let status = Command::new("git")
+        .arg("--git-dir").arg(repo.path())
+        .arg("verify-commit").arg(c.id().to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("cannot run git verify-commit: {}", e))?;
+    Ok(status.success())
+}
+
+// Squash merges and patches carry the changes themselves, so they are checked instead of
+// the commits a merge brings in
+fn is_compliant(repo: &Repository, pr: &PullRequestInfo) -> Result<bool, String> {
+    let find = |sha: &str| git2::Oid::from_str(sha).and_then(|oid| repo.find_commit(oid)).map_err(|e| e.to_string());
+    let merge = find(&pr.sha)?;
+    let commits = if merge.parent_count() >= 2 {
+        merged_commits(repo, &merge).map_err(|e| e.to_string())?.into_iter().map(|c| c.sha).collect()
+    } else if pr.commits.is_empty() {
+        vec![pr.sha.clone()]
+    } else {
+        pr.commits.iter().map(|c| c.sha.clone()).collect()
+    };
+    for sha in commits {
+        if !is_compliant_commit(repo, &find(&sha)?)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+pub fn split_by_compliance(repo: &Repository, title: Option<String>, pull_requests: Vec<PullRequestInfo>)
+    -> Result<Sections, String>
+{
+    let mut compliant = Vec::new();
+    let mut non_compliant = Vec::new();
+    for pr in pull_requests {
+        if is_compliant(repo, &pr)? {
+            compliant.push(pr);
+        } else {
+            non_compliant.push(pr);
+        }
+    }
+
+    let section_title = |name: &str| Some(match title {
+        Some(ref title) => format!("{}: {}", title, name),
+        None => name.to_string()
+    });
+    Ok(vec![
+        (section_title("Verified signatures and signed off"), compliant),
+        (section_title("Unverified signatures or missing sign-off"), non_compliant)
+    ])
+}
+
+pub fn split_by_group(group_by: GroupBy, titles: &groups::GroupTitles, title: Option<String>,
+                  pull_requests: Vec<PullRequestInfo>) -> Sections {
+    let mut grouped: HashMap<&str, Vec<PullRequestInfo>> = HashMap::new();
+    for pr in pull_requests {
+        let group = groups::title_of(titles, group_by.keys(&pr).into_iter());
+        grouped.entry(group).or_default().push(pr);
+    }
+
+    groups::ordered_titles(titles).into_iter()
+        .filter_map(|group| grouped.remove(group).map(|prs| (Some(match title {
+            Some(ref title) => format!("{}: {}", title, group),
+            None => group.to_string()
+        }), prs)))
+        .collect()
+}
+
+// All keys sort ascending; the sort is stable, so pull requests by the same author stay
+// newest first
+pub fn sort_pull_requests(pull_requests: &mut [PullRequestInfo], key: SortKey) {
+    match key {
+        SortKey::PrNumber => pull_requests.sort_by_key(|pr| pr.id),
+        SortKey::Date => pull_requests.sort_by_key(|pr| pr.time),
+        SortKey::Author => pull_requests.sort_by_key(|pr| pr.author.to_lowercase())
+    }
+}
+
+fn run_author_check(cmd: &str, author: &str) -> Result<bool, String> {
+    let status = Command::new("sh")
+        .arg("-c").arg(cmd).arg("sh").arg(author)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| format!("cannot run author check command: {}", e))?;
+    Ok(status.success())
+}
+
+// Returns authors which failed the check, marking their pull requests
+pub fn check_authors(cmd: &str, pull_requests: &mut [PullRequestInfo]) -> Result<Vec<String>, String> {
+    let mut results: HashMap<String, bool> = HashMap::new();
+    for pr in pull_requests.iter_mut() {
+        if !results.contains_key(&pr.author) {
+            let passed = run_author_check(cmd, &pr.author)?;
+            results.insert(pr.author.clone(), passed);
+        }
+        pr.author_check_failed = !results[&pr.author];
+    }
+    let mut failed: Vec<String> = results.into_iter().filter(|&(_, passed)| !passed).map(|(author, _)| author).collect();
+    failed.sort();
+    Ok(failed)
+}
+
+pub struct Enrichment {
+    pub concurrency: usize,
+    pub resume: bool,
+    pub reviewers: bool
+}
+
+// Steps which need all pull requests of the range, run once they are collected
+pub struct Processing {
+    pub handle_reverts: RevertPolicy,
+    // Host and repository path of the remote, needed by forges
+    pub remote: Option<(String, String)>,
+    pub enrich: Option<Enrichment>,
+    // Target branch and marker of --backport-candidates
+    pub backport_candidates: Option<(String, String)>,
+    pub author_check_cmd: Option<String>,
+    pub categories: groups::GroupTitles,
+    pub sort: Option<SortKey>,
+    pub reverse: bool
+}
+
+pub struct Processed {
+    pub pull_requests: Vec<PullRequestInfo>,
+    pub other_commits: OtherCommits,
+    // References of pull requests merged more than once, which are listed for their latest merge
+    pub duplicates: Vec<String>,
+    pub failed_authors: Vec<String>
+}
+
+pub fn process(repo: &Repository, oids: &[git2::Oid], pull_requests: Vec<PullRequestInfo>, mut other_commits: OtherCommits,
+               processing: &Processing, config: &Config) -> Result<Processed, Error> {
+    let pull_requests = handle_reverts(repo, oids, pull_requests, processing.handle_reverts, config)
+        .map_err(|e| Error::Revwalk(format!("cannot check reverts: {}", e)))?;
+    let (mut pull_requests, duplicates) = dedup_pull_requests(pull_requests);
+
+    if let (Some(enrichment), Some((host, repository))) = (processing.enrich.as_ref(), processing.remote.as_ref()) {
+        // Merges may come from different forges, e.g. after a migration, so each provider found
+        // in the messages gets its own
+        let mut providers = Vec::new();
+        for pr in &pull_requests {
+            if !providers.contains(&pr.provider) {
+                providers.push(pr.provider);
+            }
+        }
+        let mut enriched = false;
+        for provider in providers {
+            if let Some(forge) = forge::for_provider(provider, host, repository) {
+                forge::enrich(forge, &mut pull_requests, enrichment.concurrency, enrichment.resume, enrichment.reviewers);
+                enriched = true;
+            }
+        }
+        if !enriched && !pull_requests.is_empty() && !config.quiet {
+            warn!("None of the pull requests come from a forge which --enrich can fetch details from");
+        }
+    }
+
+    if let EmptyTitlePolicy::Api = config.empty_title_policy {
+        let (host, repository) = processing.remote.as_ref()
+            .ok_or_else(|| Error::Usage("--empty-title-policy api needs the repository of the remote".into()))?;
+        fetch_empty_titles(&mut pull_requests, host, repository, config);
+    }
+
+    // Labels are only known after enrichment
+    if let Some((ref branch, ref marker)) = processing.backport_candidates {
+        pull_requests = backport_candidates(repo, branch, marker, pull_requests, config).map_err(Error::Revwalk)?;
+    }
+
+    let failed_authors = match processing.author_check_cmd {
+        Some(ref cmd) => check_authors(cmd, &mut pull_requests).map_err(Error::Io)?,
+        None => Vec::new()
+    };
+
+    categorize(&mut pull_requests, &processing.categories);
+
+    if config.show_first_release {
+        let tags = tags_by_time(repo);
+        for pr in &mut pull_requests {
+            let oid = git2::Oid::from_str(&pr.sha).unwrap();
+            pr.first_release = first_release(repo, &tags, oid).map_err(|e| Error::Revwalk(format!("cannot check tags: {}", e)))?;
+        }
+    }
+
+    if config.output_format.oldest_first() {
+        if (processing.sort.is_some() || processing.reverse) && !config.quiet {
+            warn!("--sort and --reverse are ignored, cherry-picks follow the order of the merges");
+        }
+        let order = merge_order(repo, oids).map_err(|e| Error::Revwalk(format!("cannot order merges: {}", e)))?;
+        let position = |sha: &str| git2::Oid::from_str(sha).ok().and_then(|oid| order.get(&oid).cloned());
+        pull_requests.sort_by_key(|pr| position(&pr.sha));
+        other_commits.plain_merges.sort_by_key(|c| position(&c.sha));
+        other_commits.direct_commits.sort_by_key(|c| position(&c.sha));
+        other_commits.other_changes.sort_by_key(|c| position(&c.sha));
+    } else {
+        if let Some(key) = processing.sort {
+            sort_pull_requests(&mut pull_requests, key);
+        }
+        if processing.reverse {
+            pull_requests.reverse();
+        }
+    }
+
+    Ok(Processed { pull_requests, other_commits, duplicates, failed_authors })
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Detail {
+    Full,
+    Ids,
+    Counts
+}
+
+fn summary_item(n: usize, what: &str, ids: Option<String>, config: &Config) -> String {
+    let mut r = format!("{} {}", n, what);
+    match ids {
+        Some(ids) => write!(&mut r, ": {}", ids).unwrap(),
+        None => if let Some(ref url) = config.announce_url {
+            write!(&mut r, ", see {}", url).unwrap();
+        }
+    }
+    config.output_format.format_item(&r)
+}
+
+// Commits listed after the pull requests, each kind in its own section
+pub struct OtherCommits {
+    pub plain_merges: Vec<CommitInfo>,
+    pub direct_commits: Vec<CommitInfo>,
+    pub other_changes: Vec<CommitInfo>
+}
+
+pub fn render_sections(sections: &[(Option<String>, Vec<PullRequestInfo>)], other_commits: &OtherCommits,
+                   feature_flags: &BTreeMap<(flags::Change, String), Vec<String>>,
+                   sponsors: &BTreeMap<String, orgs::OrgContribution>,
+                   config: &Config, detail: Detail) -> Vec<String> {
+    let OtherCommits { ref plain_merges, ref direct_commits, ref other_changes } = *other_commits;
+    let mut rendered: Vec<String> = Vec::new();
+    for (title, prs) in sections {
+        let mut section = String::new();
+        if let Some(ref title) = *title {
+            writeln!(&mut section, "{}", config.output_format.format_section_header(title, config)).unwrap();
+        }
+        match detail {
+            Detail::Full => for pr in prs {
+                writeln!(&mut section, "{}", config.output_format.format(pr, config)).unwrap();
+            },
+            _ if prs.is_empty() => {}
+            Detail::Ids => {
+                let ids = prs.iter().map(|pr| pr.reference()).join(", ");
+                writeln!(&mut section, "{}", summary_item(prs.len(), "pull requests", Some(ids), config)).unwrap();
+            }
+            Detail::Counts =>
+                writeln!(&mut section, "{}", summary_item(prs.len(), "pull requests", None, config)).unwrap()
+        }
+        rendered.push(section);
+    }
+
+    if !plain_merges.is_empty() {
+        let mut section = String::new();
+        if detail == Detail::Full {
+            for merge in plain_merges {
+                writeln!(&mut section, "{}", config.output_format.format_commit(merge, config)).unwrap();
+            }
+        } else {
+            writeln!(&mut section, "{}", summary_item(plain_merges.len(), "other merges", None, config)).unwrap();
+        }
+        rendered.push(section);
+    }
+
+    if !direct_commits.is_empty() {
+        let mut section = String::new();
+        writeln!(&mut section, "{}", config.output_format.format_section_header("Direct commits", config)).unwrap();
+        if detail == Detail::Full {
+            for commit in direct_commits {
+                writeln!(&mut section, "{}", config.output_format.format_commit(commit, config)).unwrap();
+            }
+        } else {
+            writeln!(&mut section, "{}", summary_item(direct_commits.len(), "direct commits", None, config)).unwrap();
+        }
+        rendered.push(section);
+    }
+
+    if !other_changes.is_empty() {
+        let mut section = String::new();
+        writeln!(&mut section, "{}", config.output_format.format_section_header("Other changes", config)).unwrap();
+        if detail == Detail::Full {
+            for commit in other_changes {
+                writeln!(&mut section, "{}", config.output_format.format_change(commit, config)).unwrap();
+            }
+        } else {
+            writeln!(&mut section, "{}", summary_item(other_changes.len(), "other changes", None, config)).unwrap();
+        }
+        rendered.push(section);
+    }
+
+    if !feature_flags.is_empty() {
+        let mut section = String::new();
+        writeln!(&mut section, "{}", config.output_format.format_section_header("Feature flags", config)).unwrap();
+        match detail {
+            Detail::Full => for (&(change, ref name), ids) in feature_flags {
+                writeln!(&mut section, "{}", config.output_format.format_item(
+                    &format!("{} `{}` ({})", change.description(), name, ids.join(", ")))).unwrap();
+            },
+            Detail::Ids => for &change in &[flags::Change::Added, flags::Change::Removed] {
+                let names = feature_flags.keys().filter(|k| k.0 == change).map(|k| &k.1).join(", ");
+                if !names.is_empty() {
+                    writeln!(&mut section, "{}", config.output_format.format_item(
+                        &format!("{}: {}", change.description(), names))).unwrap();
+                }
+            },
+            Detail::Counts =>
+                writeln!(&mut section, "{}", summary_item(feature_flags.len(), "feature flag changes", None, config)).unwrap()
+        }
+        rendered.push(section);
+    }
+
+    if !sponsors.is_empty() {
+        let mut section = String::new();
+        writeln!(&mut section, "{}", config.output_format.format_section_header("Thanks to our sponsors", config)).unwrap();
+        if detail == Detail::Full {
+            for (org, contribution) in sponsors {
+                let authors = contribution.authors.iter().join(", ");
+                writeln!(&mut section, "{}", config.output_format.format_item(
+                    &format!("{} ({} pull requests by {})", org, contribution.pull_requests, authors))).unwrap();
+            }
+        } else {
+            writeln!(&mut section, "{}", config.output_format.format_item(&sponsors.keys().join(", "))).unwrap();
+        }
+        rendered.push(section);
+    }
+
+    rendered
+}
+
+// How the pull requests are split into sections and how long the notes may get
+pub struct Layout {
+    pub split_by_range: bool,
+    pub split_by_tag: bool,
+    // Used when grouping, see Config::group_by
+    pub group_titles: groups::GroupTitles,
+    pub compliance: bool,
+    pub breaking_changes: bool,
+    pub deprecations: bool,
+    pub max_length: Option<usize>,
+    pub allow_empty: bool
+}
+
+pub fn arrange(repo: &Repository, walks: &[(String, Vec<git2::Oid>)], oids: &[git2::Oid],
+               mut pull_requests: Vec<PullRequestInfo>, layout: &Layout, config: &Config) -> Result<Sections, Error> {
+    let breaking_changes = if layout.breaking_changes {
+        extract_breaking_changes(&mut pull_requests)
+    } else {
+        Vec::new()
+    };
+    let deprecations = if layout.deprecations {
+        extract_deprecations(&mut pull_requests)
+    } else {
+        Vec::new()
+    };
+
+    let mut sections: Sections = if layout.split_by_range {
+        split_by_range(walks, pull_requests).into_iter().map(|(title, prs)| (Some(title), prs)).collect()
+    } else if layout.split_by_tag {
+        let in_range: HashSet<git2::Oid> = oids.iter().cloned().collect();
+        let mut sections = split_by_tag(repo, &in_range, pull_requests, config)
+            .map_err(|e| Error::Revwalk(format!("cannot check tags: {}", e)))?;
+        if config.output_format.oldest_first() {
+            sections.reverse();
+        }
+        sections.into_iter().map(|(title, prs)| (Some(title), prs)).collect()
+    } else {
+        vec![(None, pull_requests)]
+    };
+
+    if config.group_by != GroupBy::Nothing {
+        sections = sections.into_iter()
+            .flat_map(|(title, prs)| split_by_group(config.group_by, &layout.group_titles, title, prs).into_iter())
+            .collect();
+    }
+
+    if layout.compliance {
+        let mut split = Vec::new();
+        for (title, prs) in sections {
+            split.extend(split_by_compliance(repo, title, prs)
+                         .map_err(|e| Error::Repository(format!("cannot check signatures: {}", e)))?);
+        }
+        sections = split;
+    }
+
+    if !deprecations.is_empty() {
+        sections.insert(0, (Some("Deprecations and removals".into()), deprecations));
+    }
+    if !breaking_changes.is_empty() {
+        sections.insert(0, (Some("Breaking changes".into()), breaking_changes));
+    }
+    Ok(sections)
+}
+
+// Falls back to less detailed notes when they are longer than --max-length
+pub fn render_notes(sections: &Sections, other_commits: &OtherCommits,
+                    feature_flags: &BTreeMap<(flags::Change, String), Vec<String>>,
+                    sponsors: &BTreeMap<String, orgs::OrgContribution>,
+                    layout: &Layout, config: &Config) -> Vec<String> {
+    let mut rendered = render_sections(sections, other_commits, feature_flags, sponsors, config, Detail::Full);
+    if let Some(max_length) = layout.max_length {
+        for &detail in &[Detail::Ids, Detail::Counts] {
+            let length = rendered.iter().fold(0, |acc, s| acc + s.chars().count());
+            if length <= max_length {
+                break;
+            }
+            info!("Output is {} characters long, which is more than {}, summarizing it", length, max_length);
+            rendered = render_sections(sections, other_commits, feature_flags, sponsors, config, detail);
+        }
+    }
+
+    if layout.allow_empty && sections.iter().all(|(_, prs)| prs.is_empty()) {
+        rendered.insert(0, format!("{}\n", config.output_format.format_item("No user-facing changes")));
+    }
+    rendered
+}
+
+pub fn translate_section(config: &Config, text: String) -> Result<String, String> {
+    use std::io::Write;
+
+    let cmd = match config.translate_cmd {
+        Some(ref cmd) => cmd,
+        None => return Ok(text)
+    };
+
+    let mut child = Command::new("sh")
+        .arg("-c").arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run translation command: {}", e))?;
+    // The command may start writing before it has read everything, so the input is fed
+    // from another thread while the output is collected here
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(text.as_bytes()));
+
+    let output = child.wait_with_output().map_err(|e| format!("translation command failed: {}", e))?;
+    match writer.join().unwrap() {
+        Err(ref e) if e.kind() != std::io::ErrorKind::BrokenPipe =>
+            return Err(format!("cannot write to translation command: {}", e)),
+        _ => {}
+    }
+    if !output.status.success() {
+        return Err(format!("translation command exited with {}", output.status));
+    }
+    String::from_utf8(output.stdout).map_err(|e| format!("translation command produced invalid UTF-8: {}", e))
+}
+
+// Returns pull requests marked as breaking by a ! after the type in a conventional commit style
+// title, a BREAKING CHANGE: footer, whose text becomes the excerpt, or a breaking label
+pub fn extract_breaking_changes(pull_requests: &mut Vec<PullRequestInfo>) -> Vec<PullRequestInfo> {
+    let footer_pattern = regex!(r"^BREAKING[ -]CHANGE:\s*(.*)$");
+
+    let mut breaking = Vec::new();
+    let mut rest = Vec::new();
+    for mut pr in pull_requests.drain(..) {
+        let footer = pr.body.lines().filter_map(|l| footer_pattern.captures(l.trim())).next()
+            .map(|captures| truncate_chars(captures[1].trim(), 200));
+        let labelled = pr.labels.iter().any(|l| l.to_lowercase().starts_with("breaking"));
+        let marked = parse_conventional_title(&pr.name).is_some_and(|(_, breaking)| breaking);
+        if footer.is_some() || labelled || marked {
+            if let Some(footer) = footer.filter(|f| !f.is_empty()) {
+                pr.excerpt = Some(footer);
+            }
+            breaking.push(pr);
+        } else {
+            rest.push(pr);
+        }
+    }
+    *pull_requests = rest;
+    breaking
+}
+
+// Returns pull requests with a body line or title starting with a "Deprecated:", "Deprecation:",
+// "Removed:" or "Removal:" marker, whose text becomes the excerpt, or with a deprecation label;
+// merely mentioning removals in the prose doesn't count
+pub fn extract_deprecations(pull_requests: &mut Vec<PullRequestInfo>) -> Vec<PullRequestInfo> {
+    let marker_pattern = regex!(r"(?i)^(?:deprecat(?:ed|ions?)|remov(?:ed|als?))\s*:\s*(.*)$");
+
+    let mut deprecations = Vec::new();
+    let mut rest = Vec::new();
+    for mut pr in pull_requests.drain(..) {
+        let marker = pr.body.lines().filter_map(|l| marker_pattern.captures(l.trim())).next()
+            .map(|captures| truncate_chars(captures[1].trim(), 200));
+        let labelled = pr.labels.iter().any(|l| l.to_lowercase().starts_with("deprecat"));
+        if marker.is_some() || labelled || marker_pattern.is_match(&pr.name) {
+            if let Some(marker) = marker.filter(|m| !m.is_empty() && *m != pr.name) {
+                pr.excerpt = Some(marker);
+            }
+            deprecations.push(pr);
+        } else {
+            rest.push(pr);
+        }
+    }
+    *pull_requests = rest;
+    deprecations
+}
+
+pub struct Preview {
+    pub commits: usize,
+    pub merges: usize,
+    pub pull_requests: usize,
+    // Times of the oldest and the newest commit
+    pub span: Option<(i64, i64)>
+}
+
+impl fmt::Display for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Commits:       {}", self.commits)?;
+        writeln!(f, "Merges:        {}", self.merges)?;
+        writeln!(f, "Pull requests: {}", self.pull_requests)?;
+        if let Some((first, last)) = self.span {
+            writeln!(f, "Date span:     {} to {} ({} days)", format_time(first, "%Y-%m-%d"), format_time(last, "%Y-%m-%d"),
+                     (last - first) / (24 * 60 * 60))?;
+        }
+        Ok(())
+    }
+}
+
+// Only looks at commit headers, so that the range can be checked quickly before a full run
+pub fn preview(repo: &Repository, oids: &[git2::Oid], config: &Config) -> Result<Preview, git2::Error> {
+    let mut merges = 0;
+    let mut pull_requests = 0;
+    let mut series = HashSet::new();
+    let mut first = i64::MAX;
+    let mut last = i64::MIN;
+    for &oid in oids {
+        let c = repo.find_commit(oid)?;
+        let time = c.time().seconds();
+        first = cmp::min(first, time);
+        last = cmp::max(last, time);
+        if c.parents().len() >= 2 {
+            merges += 1;
+        }
+        match pull_request_kind(&c, config) {
+            Some(PullRequestKind::Patch) => {
+                if let Some((key, _, _)) = c.message().and_then(parse_patch_link) {
+                    series.insert(key);
+                }
+            }
+            Some(_) => pull_requests += 1,
+            None => {}
+        }
+    }
+    pull_requests += series.len();
+
+    Ok(Preview {
+        commits: oids.len(),
+        merges,
+        pull_requests,
+        span: if oids.is_empty() { None } else { Some((first, last)) }
+    })
+}
+
+// Splits the items into at most `jobs` chunks which are processed on separate threads;
+// the results keep the order of the items
+fn parallel_chunks<T, R, F>(items: Vec<T>, jobs: usize, f: F) -> Vec<R>
+    where T: Send + 'static, R: Send + 'static, F: Fn(Vec<T>) -> Vec<R> + Send + Sync + 'static
+{
+    let jobs = cmp::max(1, cmp::min(jobs, items.len()));
+    if jobs == 1 {
+        return f(items);
+    }
+
+    let chunk_size = items.len().div_ceil(jobs);
+    let mut chunks = Vec::new();
+    let mut items = items.into_iter();
+    loop {
+        let chunk: Vec<T> = items.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        chunks.push(chunk);
+    }
+
+    let f = Arc::new(f);
+    let handles: Vec<_> = chunks.into_iter().map(|chunk| {
+        let f = f.clone();
+        thread::spawn(move || f(chunk))
+    }).collect();
+    handles.into_iter().flat_map(|h| h.join().unwrap().into_iter()).collect()
+}
+
+// Diff of the pull request against the first parent, i.e. everything it brought into the branch
+fn pull_request_diff<'a>(repo: &'a Repository, sha: &str) -> Result<git2::Diff<'a>, git2::Error> {
+    let commit = repo.find_commit(git2::Oid::from_str(sha)?)?;
+    let new_tree = commit.tree()?;
+    let old_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree()?),
+        None => None
+    };
+    repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), None)
+}
+
+pub struct FoundMerge {
+    pub commit: git2::Oid,
+    pub time: i64,
+    pub tags: Vec<String>
+}
+
+impl fmt::Display for FoundMerge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "commit {}", self.commit)?;
+        writeln!(f, "merged {}", format_time(self.time, "%Y-%m-%d %H:%M:%S"))?;
+        if self.tags.is_empty() {
+            writeln!(f, "not contained in any tag")
+        } else {
+            writeln!(f, "contained in {}", self.tags.join(", "))
+        }
+    }
+}
+
+pub fn find_pr(repo: &Repository, id: u32, range: &str, config: &Config) -> Result<FoundMerge, String> {
+    let mut revwalk = repo.revwalk().map_err(|e| format!("cannot get revwalk: {}", e))?;
+    push_range(&mut revwalk, repo, range).map_err(|e| format!("error pushing range {}: {}", range, e))?;
+    revwalk.set_sorting(git2::Sort::TIME).map_err(|e| format!("cannot sort revwalk: {}", e))?;
+
+    for oid in revwalk {
+        let oid = oid.map_err(|e| format!("cannot walk history: {}", e))?;
+        let c = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+        let time = c.time().seconds();
+        let found = match pull_request_of(repo, c, config) {
+            Ok(Some(pr)) => pr.id == id,
+            _ => false
+        };
+        if found {
+            let tags = containing_tags(repo, oid).map_err(|e| format!("cannot check tags: {}", e))?;
+            return Ok(FoundMerge { commit: oid, time, tags });
+        }
+    }
+    if config.include_squash {
+        Err(format!("pull request #{} not found in {}", id, range))
+    } else {
+        Err(format!("pull request #{} not found in {}; squash merges are only recognized with --include-squash", id, range))
+    }
+}
+
+// How a commit brought a pull request in; every command recognizes pull requests the same way,
+// with squash merges and patches only when asked for
+#[derive(Copy, Clone, PartialEq)]
+enum PullRequestKind {
+    Merge,
+    Squash,
+    Patch
+}
+
+fn pull_request_kind<'a>(c: &git2::Commit<'a>, config: &Config) -> Option<PullRequestKind> {
+    if c.parents().len() >= 2 {
+        return match split_message(c) {
+            Ok((header, body)) if parse_merge_message(&header, &body, config).is_some() => Some(PullRequestKind::Merge),
+            _ => None
+        };
+    }
+    if config.patchsets && c.message().and_then(parse_patch_link).is_some() {
+        Some(PullRequestKind::Patch)
+    } else if config.include_squash && is_squash(c) {
+        Some(PullRequestKind::Squash)
+    } else {
+        None
+    }
+}
+
+fn is_pull_request<'a>(c: &git2::Commit<'a>, config: &Config) -> bool {
+    pull_request_kind(c, config).is_some()
+}
+
+// The pull request a single commit brought in, with a patch standing for its whole series
+fn pull_request_of<'a>(repo: &Repository, c: git2::Commit<'a>, config: &Config) -> Result<Option<PullRequestInfo>, String> {
+    match pull_request_kind(&c, config) {
+        Some(PullRequestKind::Merge) => PullRequestInfo::from_commit(repo, c, config).map(Some).map_err(|e| e.to_string()),
+        Some(PullRequestKind::Squash) => Ok(PullRequestInfo::from_squash_commit(c, config)),
+        Some(PullRequestKind::Patch) => PullRequestInfo::from_patch_series(repo, &[c.id()], config).map(Some)
+            .map_err(|e| format!("cannot read patch series: {}", e)),
+        None => Ok(None)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum SkipReason {
+    NoMessage,
+    InvalidHeader,
+    InvalidId,
+    Octopus,
+    EmptyTitle
+}
+
+impl SkipReason {
+    fn description(self) -> &'static str {
+        match self {
+            SkipReason::NoMessage => "missing message",
+            SkipReason::InvalidHeader => "invalid header line",
+            SkipReason::InvalidId => "invalid pull request id",
+            SkipReason::Octopus => "octopus merge",
+            SkipReason::EmptyTitle => "empty title"
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            SkipReason::NoMessage => "missing_message",
+            SkipReason::InvalidHeader => "invalid_header",
+            SkipReason::InvalidId => "invalid_id",
+            SkipReason::Octopus => "octopus",
+            SkipReason::EmptyTitle => "empty_title"
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ParseError {
+    reason: SkipReason,
+    message: String
+}
+
+impl ParseError {
+    fn new(reason: SkipReason, message: String) -> ParseError {
+        ParseError {
+            reason,
+            message
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub commits: usize,
+    pub merges: usize,
+    pub pull_requests: usize,
+    pub skipped: BTreeMap<SkipReason, usize>
+}
+
+impl Summary {
+    fn skip(&mut self, reason: SkipReason) {
+        *self.skipped.entry(reason).or_insert(0) += 1;
+    }
+
+    pub fn to_json(&self) -> Value {
+        let skipped: Map<String, Value> = self.skipped.iter().map(|(reason, &n)| (reason.key().to_string(), json!(n))).collect();
+        json!({
+            "commits": self.commits,
+            "merges": self.merges,
+            "pull_requests": self.pull_requests,
+            "skipped": skipped
+        })
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let skipped: usize = self.skipped.values().sum();
+        writeln!(f, "Summary: {} commits walked, {} merge commits, {} pull requests found, {} skipped",
+                 self.commits, self.merges, self.pull_requests, skipped)?;
+        for (reason, count) in &self.skipped {
+            writeln!(f, "  {}: {}", reason.description(), count)?;
+        }
+        Ok(())
+    }
+}
+
+// Custom header patterns must have an `id` group and may have `author` and `branch` groups
+fn parse_custom_header(header: &str, pattern: &Regex) -> Option<Result<ParsedHeader, String>> {
+    pattern.captures(header.trim()).map(|captures| {
+        let id = captures.name("id").map_or("", |m| m.as_str());
+        match id.parse() {
+            Ok(id) => Ok((id, captures.name("author").map(|m| m.as_str().to_string()),
+                          captures.name("branch").map_or("", |m| m.as_str()).to_string())),
+            Err(e) => Err(format!("invalid pull request id {:?}: {}", id, e))
+        }
+    })
+}
+
+// Returns the provider, pull request id, author and branch; GitLab and Bitbucket messages, and
+// Gitea ones for pull requests from the same repository, don't name the author, so it is None
+// for them
+fn parse_merge_message(header: &str, body: &str, config: &Config)
+    -> Option<Result<ParsedMessage, String>>
+{
+    let provider = config.provider;
+    for pattern in &config.header_patterns {
+        if let Some(parsed) = parse_custom_header(header, pattern) {
+            let custom_provider = if provider == Provider::Auto { Provider::GitHub } else { provider };
+            return Some(parsed.map(|(id, author, branch)| (custom_provider, id, author, branch)));
+        }
+    }
+    if provider.accepts(Provider::GitHub) {
+        if let Some(parsed) = github::parse_header(header) {
+            return Some(parsed.map(|(id, author, branch)| (Provider::GitHub, id, Some(author), branch)));
+        }
+    }
+    if provider.accepts(Provider::GitLab) {
+        if let Some(parsed) = gitlab::parse_message(header, body) {
+            return Some(parsed.map(|(id, branch)| (Provider::GitLab, id, None, branch)));
+        }
+    }
+    if provider.accepts(Provider::Gitea) {
+        if let Some(parsed) = gitea::parse_header(header) {
+            return Some(parsed.map(|((id, author, branch), _)| (Provider::Gitea, id, author, branch)));
+        }
+    }
+    if provider.accepts(Provider::Bitbucket) {
+        if let Some(parsed) = bitbucket::parse_header(header) {
+            return Some(parsed.map(|(id, branch)| (Provider::Bitbucket, id, None, branch)));
+        }
+    }
+    None
+}
+
+fn parse_squash_header(header: &str) -> Option<(String, u32)> {
+    let squash_pattern = regex!(r"^(.+) \(#(\d+)\)$");
+    squash_pattern.captures(header.trim()).and_then(|captures| {
+        captures[2].parse().ok().map(|id| (captures[1].to_string(), id))
+    })
+}
+
+// The patch series a commit applied from lists.sr.ht belongs to, judging by its Link: trailer;
+// returns a key identifying the series, its id and the link. Links to a patchset carry the
+// id, links to a patch email only its message id, which git send-email numbers per patch
+fn parse_patch_link(message: &str) -> Option<(String, u32, String)> {
+    let link_pattern = regex!(r"(?m)^Link: (https://lists\.sr\.ht/(~[^/\s]+/[^/\s]+)/(\S+?))/?$");
+    let message_id_pattern = regex!(r"^(.+?)-\d+-(.+)$");
+
+    let captures = link_pattern.captures(message)?;
+    let (url, list, rest) = (&captures[1], &captures[2], &captures[3]);
+    if let Some(id) = rest.strip_prefix("patches/").and_then(|id| id.parse().ok()) {
+        return Some((format!("{}/patches/{}", list, id), id, url.to_string()));
+    }
+    let message_id = rest.replace("%3C", "").replace("%3c", "").replace("%3E", "").replace("%3e", "")
+        .replace("%40", "@").replace(['<', '>'], "");
+    let series = message_id_pattern.replace(&message_id, "$1-$2");
+    Some((format!("{}/{}", list, series), 0, url.to_string()))
+}
+
+// The subject of a patch without its `[PATCH v2 1/3]` style prefix
+fn patch_title(header: &str) -> String {
+    regex!(r"^\[[^\]]*PATCH[^\]]*\]\s*").replace(header, "").into()
+}
+
+fn is_patch_trailer(line: &str) -> bool {
+    ["Link:", "Signed-off-by:", "Acked-by:", "Reviewed-by:", "Tested-by:", "Cc:"].iter().any(|t| line.starts_with(t))
+}
+
+fn is_squash<'a>(c: &git2::Commit<'a>) -> bool {
+    c.parents().len() == 1 && match split_message(c) {
+        Ok((header, _)) => parse_squash_header(&header).is_some(),
+        Err(_) => false
+    }
+}
+
+fn split_message<'a>(c: &git2::Commit<'a>) -> Result<(String, String), ParseError> {
+    let msg = match c.message() {
+        Some(msg) => msg,
+        None => return Err(ParseError::new(SkipReason::NoMessage,
+                                           format!("cannot get commit message for commit {}", c.id())))
+    };
+
+    let mut lines_iter = msg.lines();
+    let header = match lines_iter.next() {
+        Some(header) => header.into(),
+        None => return Err(ParseError::new(SkipReason::NoMessage, format!("merge commit {} has empty message", c.id())))
+    };
+    let body = lines_iter.join("\n");
+    Ok((header, clean_body(&body)))
+}
+
+impl CommitInfo {
+    pub fn to_json(&self) -> Value {
+        let mut obj = Map::new();
+        obj.insert("sha".to_string(), json!(self.sha));
+        obj.insert("author".to_string(), json!(self.author));
+        obj.insert("summary".to_string(), json!(self.summary));
+        Value::Object(obj)
+    }
+}
+
+impl PullRequestInfo {
+    pub fn to_json(&self) -> Value {
+        let mut obj = Map::new();
+        obj.insert("id".to_string(), json!(self.id));
+        obj.insert("provider".to_string(), json!(self.provider.name()));
+        obj.insert("author".to_string(), json!(self.author));
+        obj.insert("branch".to_string(), json!(self.branch));
+        obj.insert("title".to_string(), json!(self.name));
+        obj.insert("sha".to_string(), json!(self.sha));
+        obj.insert("date".to_string(), json!(format_time_utc(self.time, "%Y-%m-%dT%H:%M:%SZ")));
+        if !self.commits.is_empty() {
+            obj.insert("commits".to_string(), Value::Array(self.commits.iter().map(CommitInfo::to_json).collect()));
+        }
+        if let Some(ref release) = self.first_release {
+            obj.insert("first_release".to_string(), json!(release));
+        }
+        if !self.labels.is_empty() {
+            obj.insert("labels".to_string(), json!(self.labels));
+        }
+        if let Some(ref milestone) = self.milestone {
+            obj.insert("milestone".to_string(), json!(milestone));
+        }
+        if let Some(ref merged_by) = self.merged_by {
+            obj.insert("merged_by".to_string(), json!(merged_by));
+        }
+        if let Some(ref association) = self.author_association {
+            obj.insert("author_association".to_string(), json!(association));
+        }
+        if let Some(ref reviewers) = self.reviewers {
+            obj.insert("reviewers".to_string(), json!(reviewers));
+        }
+        if let Some(ref email) = self.email {
+            obj.insert("email".to_string(), json!(email));
+        }
+        if let Some(ref reverted_by) = self.reverted_by {
+            obj.insert("reverted_by".to_string(), json!(reverted_by));
+        }
+        if let Some(ref url) = self.url {
+            obj.insert("url".to_string(), json!(url));
+        }
+        if let Some(ref category) = self.category {
+            obj.insert("category".to_string(), json!(category));
+        }
+        Value::Object(obj)
+    }
+}
+
+pub fn pull_requests_json(pull_requests: &[PullRequestInfo]) -> Value {
+    Value::Array(pull_requests.iter().map(PullRequestInfo::to_json).collect())
+}
+
+impl PullRequestInfo {
+    fn from_commit<'a>(repo: &Repository, c: git2::Commit<'a>, config: &Config) -> Result<PullRequestInfo, ParseError> {
+        let (header, body) = split_message(&c)?;
+
+        let (provider, id, author, branch) = match parse_merge_message(&header, &body, config) {
+            Some(Ok(parts)) => parts,
+            Some(Err(e)) => return Err(ParseError::new(SkipReason::InvalidId, format!("merge commit {} has {}", c.id(), e))),
+            None => return Err(ParseError::new(SkipReason::InvalidHeader,
+                                               format!("merge commit {} has invalid pull request header line: {}", c.id(), header)))
+        };
+
+        // The second parent is the head of the merged branch, so its author is the one who
+        // made the pull request
+        let head = c.parent_ids().nth(1).and_then(|p| repo.find_commit(p).ok());
+        let author = match author {
+            Some(author) => author,
+            None => head.as_ref().map_or(String::new(), |p| p.author().name().unwrap_or("").into())
+        };
+        let email = if config.show_email {
+            head.as_ref().and_then(|p| p.author().email().map(|e| e.to_string()))
+        } else {
+            None
+        };
+        let body = match provider {
+            Provider::GitLab => body.lines().filter(|l| !l.starts_with("See merge request ")).join("\n").trim().into(),
+            Provider::Gitea => body.lines()
+                .filter(|l| !l.starts_with("Reviewed-on: ") && !l.starts_with("Reviewed-by: "))
+                .join("\n").trim().into(),
+            Provider::Bitbucket => body.lines().filter(|l| !l.starts_with("Approved-by: ")).join("\n").trim().into(),
+            _ => body
+        };
+        // A title in the header is the pull request title, the body is its description then;
+        // Bitbucket puts the title on the first line of the body instead
+        let (header_title, body) = match provider {
+            Provider::Gitea => (gitea::parse_header(&header).and_then(|parsed| parsed.ok()).and_then(|(_, title)| title), body),
+            Provider::Bitbucket => match body.split_once('\n') {
+                Some((title, description)) => (Some(title.trim().to_string()), description.trim().to_string()),
+                None => (forge::non_empty(Some(&body)), String::new())
+            },
+            _ => (None, body)
+        };
+
+        let commits = if config.lists_commits() {
+            match merged_commits(repo, &c) {
+                Ok(commits) => commits,
+                Err(e) => {
+                    warn!("cannot get commits of merge commit {}: {}", c.id(), e);
+                    vec![]
+                }
+            }
+        } else {
+            vec![]
+        };
+
+        Ok(PullRequestInfo {
+            provider,
+            id,
+            sha: c.id().to_string(),
+            time: c.time().seconds(),
+            author,
+            branch,
+            name: header_title.unwrap_or_else(|| config.title_from.extract(&body)),
+            body,
+            commits,
+            first_release: None,
+            author_check_failed: false,
+            excerpt: None,
+            labels: vec![],
+            milestone: None,
+            merged_by: None,
+            author_association: None,
+            reviewers: None,
+            email,
+            reverted_by: None,
+            url: None,
+            category: None
+        })
+    }
+
+    // Squash merges only carry the pull request number in the title, so the commit author
+    // is used as the pull request author and the branch is unknown
+    fn from_squash_commit<'a>(c: git2::Commit<'a>, config: &Config) -> Option<PullRequestInfo> {
+        let (header, body) = match split_message(&c) {
+            Ok(parts) => parts,
+            Err(_) => return None
+        };
+        parse_squash_header(&header).map(|(title, id)| PullRequestInfo {
+            provider: Provider::GitHub,
+            id,
+            sha: c.id().to_string(),
+            time: c.time().seconds(),
+            author: c.author().name().unwrap_or("").into(),
+            branch: String::new(),
+            name: title,
+            body,
+            commits: vec![],
+            first_release: None,
+            author_check_failed: false,
+            excerpt: None,
+            labels: vec![],
+            milestone: None,
+            merged_by: None,
+            author_association: None,
+            reviewers: None,
+            email: if config.show_email { c.author().email().map(|e| e.to_string()) } else { None },
+            reverted_by: None,
+            url: None,
+            category: None
+        })
+    }
+
+    // A patch series applied from a mailing list has no merge commit, so it is described by its
+    // first patch and dated by its last one; patches are given oldest first
+    fn from_patch_series(repo: &Repository, oids: &[git2::Oid], config: &Config) -> Result<PullRequestInfo, git2::Error> {
+        let first = repo.find_commit(oids[0])?;
+        let last = repo.find_commit(oids[oids.len() - 1])?;
+        let message = first.message().unwrap_or("");
+        let (_, id, url) = parse_patch_link(message).unwrap_or_default();
+        let header = message.lines().next().unwrap_or("").trim();
+        let author = first.author();
+        let body = clean_body(&message.lines().skip(1).filter(|l| !is_patch_trailer(l)).join("\n"));
+
+        // The patches are what got reviewed, so they are listed even without --show-commits
+        let commits = if oids.len() > 1 || config.lists_commits() {
+            oids.iter().map(|&oid| repo.find_commit(oid).map(|c| CommitInfo::from_commit(&c))).collect::<Result<_, _>>()?
+        } else {
+            vec![]
+        };
+
+        Ok(PullRequestInfo {
+            provider: Provider::SourceHut,
+            id,
+            sha: last.id().to_string(),
+            time: last.time().seconds(),
+            author: author.name().unwrap_or("").into(),
+            branch: String::new(),
+            name: patch_title(header),
+            body,
+            commits,
+            first_release: None,
+            author_check_failed: false,
+            excerpt: None,
+            labels: vec![],
+            milestone: None,
+            merged_by: None,
+            author_association: None,
+            reviewers: None,
+            email: if config.show_email { author.email().map(|e| e.to_string()) } else { None },
+            reverted_by: None,
+            url: Some(url).filter(|u| !u.is_empty()),
+            category: None
+        })
+    }
+
+    // Patch series found by --patchsets are referred to by their id, or by the SHA of their
+    // last patch when only a message id links them to the list
+    pub fn reference(&self) -> String {
+        match (self.provider, self.id) {
+            (Provider::SourceHut, 0) => self.sha[..7.min(self.sha.len())].to_string(),
+            _ => format!("{}{}", self.provider.id_prefix(), self.id)
+        }
+    }
+
+    // Octopus merges don't have a single pull request header, so every line of the message
+    // which looks like one is treated as a separate pull request with an empty description
+    fn from_octopus_commit<'a>(c: git2::Commit<'a>, config: &Config) -> Result<Vec<PullRequestInfo>, ParseError> {
+        let msg = match c.message() {
+            Some(msg) => msg,
+            None => return Err(ParseError::new(SkipReason::NoMessage,
+                                               format!("cannot get commit message for commit {}", c.id())))
+        };
+
+        // Lines are matched like the header of a regular merge, with the lines after them as
+        // the body, where GitLab's `See merge request` reference follows its header line
+        let lines: Vec<&str> = msg.lines().collect();
+        let mut result = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            match parse_merge_message(line, &lines[i + 1..].join("\n"), config) {
+                Some(Ok((provider, id, author, branch))) => result.push(PullRequestInfo {
+                    provider,
+                    id,
+                    sha: c.id().to_string(),
+                    time: c.time().seconds(),
+                    author: author.unwrap_or_default(),
+                    branch,
+                    name: match provider {
+                        Provider::Gitea => gitea::parse_header(line).and_then(|parsed| parsed.ok())
+                            .and_then(|(_, title)| title).unwrap_or_default(),
+                        _ => String::new()
+                    },
+                    body: String::new(),
+                    commits: vec![],
+                    first_release: None,
+                    author_check_failed: false,
+                    excerpt: None,
+                    labels: vec![],
+                    milestone: None,
+                    merged_by: None,
+                    author_association: None,
+                    reviewers: None,
+                    email: None,
+                    reverted_by: None,
+                    url: None,
+                    category: None
+                }),
+                Some(Err(e)) => return Err(ParseError::new(SkipReason::InvalidId,
+                                                           format!("octopus merge commit {} has {}", c.id(), e))),
+                None => {}
+            }
+        }
+
+        if result.is_empty() {
+            Err(ParseError::new(SkipReason::InvalidHeader,
+                                format!("octopus merge commit {} does not reference any pull requests", c.id())))
+        } else {
+            Ok(result)
+        }
+    }
+}
+
+// What a commit of the range turned out to be; commits are parsed on several threads and
+// the outcomes are collected in the order of the range
+enum Outcome {
+    // Parsing was interrupted or timed out before getting to the commit
+    Stopped,
+    // Outside of --since and --until, or not a merge
+    Skipped,
+    // A patch of the series with the given key
+    Patch(String),
+    Squash(Box<PullRequestInfo>),
+    PlainMerge(CommitInfo),
+    // An octopus merge which is not expanded
+    Octopus,
+    Merge(Result<Vec<PullRequestInfo>, ParseError>)
+}
+
+fn parse_commit(repo: &Repository, oid: git2::Oid, config: &Config) -> Result<Outcome, String> {
+    if cancel::stopped().is_some() {
+        return Ok(Outcome::Stopped);
+    }
+    let c = repo.find_commit(oid).map_err(|e| format!("cannot find commit {}: {}", oid, e))?;
+    if !config.in_period(&c) {
+        return Ok(Outcome::Skipped);
+    }
+    if config.patchsets && c.parents().len() < 2 {
+        if let Some((key, _, _)) = c.message().and_then(parse_patch_link) {
+            return Ok(Outcome::Patch(key));
+        }
+    }
+    // only merge commits, unless squashes are needed
+    if c.parents().len() < 2 && !config.include_squash {
+        return Ok(Outcome::Skipped);
+    }
+    if c.parents().len() < 2 {
+        return Ok(PullRequestInfo::from_squash_commit(c, config).map_or(Outcome::Skipped, |pr| Outcome::Squash(Box::new(pr))));
+    }
+
+    if config.include_plain_merges && c.parents().len() == 2 && !is_pull_request(&c, config) {
+        return Ok(Outcome::PlainMerge(CommitInfo::from_commit(&c)));
+    }
+    Ok(if c.parents().len() == 2 {
+        Outcome::Merge(PullRequestInfo::from_commit(repo, c, config).map(|pr| vec![pr]))
+    } else {
+        config.octopus.apply(c, config).map_or(Outcome::Octopus, Outcome::Merge)
+    })
+}
+
+pub struct Collection {
+    pub oids: Vec<git2::Oid>,
+    pub pull_requests: Vec<PullRequestInfo>,
+    pub plain_merges: Vec<CommitInfo>,
+    pub direct_commits: Vec<CommitInfo>,
+    pub other_changes: Vec<CommitInfo>,
+    pub summary: Summary,
+    pub invalid: usize,
+    // Invalid merge commits and octopus merges skipped with a warning
+    pub warnings: usize
+}
+
+// Turns a commit range into pull requests according to the configuration; `walk` and
+// `collect` are separate so that the walked commits can be inspected before parsing them
+pub struct Collector<'r> {
+    repo: &'r Repository,
+    config: &'r Config
+}
+
+impl<'r> Collector<'r> {
+    pub fn new(repo: &'r Repository, config: &'r Config) -> Collector<'r> {
+        Collector { repo, config }
+    }
+
+    // Returns commits in the range which are not reachable from the excluded refs, newest first
+    pub fn walk(&self, range: &str, excluded: &[String]) -> Result<Vec<git2::Oid>, String> {
+        let mut revwalk = self.repo.revwalk().map_err(|e| format!("cannot get revwalk: {}", e))?;
+
+        push_range(&mut revwalk, self.repo, range).map_err(|e| format!("error pushing range {}: {}", range, e))?;
+        for excluded in excluded {
+            let obj = self.repo.revparse_single(&format!("{}^{{commit}}", excluded))
+                .map_err(|e| format!("cannot resolve {}: {}", excluded, e))?;
+            revwalk.hide(obj.id()).map_err(|e| format!("cannot exclude {}: {}", excluded, e))?;
+        }
+        revwalk.set_sorting(git2::Sort::TIME).map_err(|e| format!("cannot sort revwalk: {}", e))?;
+        if self.config.first_parent {
+            revwalk.simplify_first_parent().map_err(|e| format!("cannot simplify revwalk: {}", e))?;
+        }
+
+        let oids: Vec<git2::Oid> = revwalk.collect::<Result<_, _>>()
+            .map_err(|e| format!("cannot walk history: {}", e))?;
+        if oids.is_empty() {
+            let parts: Vec<&str> = range.splitn(2, "..").collect();
+            return Err(if parts.len() == 2 {
+                format!("range {} is empty; check that {} is an ancestor of {} or try {}..{}",
+                        range, parts[0], parts[1], parts[1], parts[0])
+            } else {
+                format!("range {} is empty", range)
+            });
+        }
+        Ok(oids)
+    }
+
+    // Ranges are walked separately because hiding the start of one range in a shared revwalk
+    // would also hide commits of the other ranges; the merged commits are newest first
+    pub fn merge_walks(&self, walks: &[(String, Vec<git2::Oid>)]) -> Vec<git2::Oid> {
+        let mut seen = HashSet::new();
+        let mut oids: Vec<git2::Oid> = walks.iter()
+            .flat_map(|(_, oids)| oids.iter().cloned())
+            .filter(|&oid| seen.insert(oid))
+            .collect();
+        if walks.len() > 1 {
+            oids.sort_by_key(|&oid| cmp::Reverse(self.repo.find_commit(oid).map(|c| c.time().seconds()).unwrap_or(0)));
+        }
+        oids
+    }
+
+    pub fn collect(&self, ranges: &[String], oids: Vec<git2::Oid>) -> Result<Collection, String> {
+        let (repo, config) = (self.repo, self.config);
+
+        let direct_commits = if config.include_direct_commits {
+            let in_range: HashSet<git2::Oid> = oids.iter().cloned().collect();
+            let mut seen = HashSet::new();
+            let mut result = Vec::new();
+            for range in ranges {
+                let tip = range_tip(repo, range)?;
+                result.extend(direct_commits(repo, tip, &in_range, config).into_iter().filter(|c| seen.insert(c.sha.clone())));
+            }
+            result
+        } else {
+            Vec::new()
+        };
+
+        let mut summary = Summary { commits: oids.len(), ..Summary::default() };
+
+        let mut invalid = 0usize;
+        let mut warnings = 0usize;
+        let mut pull_requests: Vec<PullRequestInfo> = Vec::new();
+        let mut plain_merges: Vec<CommitInfo> = Vec::new();
+        // Commits detected as pull requests, including those skipped for an empty title
+        let mut pr_commits: Vec<git2::Oid> = Vec::new();
+        // Patches of each series applied from lists.sr.ht, newest first like the range
+        let mut patchsets: Vec<(String, Vec<git2::Oid>)> = Vec::new();
+        // Repositories can't be shared between threads, so every thread opens its own
+        let (repo_path, parse_config) = (repo.path().to_path_buf(), config.clone());
+        let outcomes = parallel_chunks(oids.clone(), config.jobs, move |chunk| {
+            let repo = match Repository::open(&repo_path) {
+                Ok(repo) => repo,
+                Err(e) => return chunk.iter().map(|_| Err(format!("cannot open repository: {}", e))).collect()
+            };
+            chunk.into_iter().map(|oid| parse_commit(&repo, oid, &parse_config)).collect()
+        });
+        for (&oid, outcome) in oids.iter().zip(outcomes) {
+            match outcome? {
+                Outcome::Stopped => {
+                    if let Some(stop) = cancel::stopped() {
+                        warn!("Parsing {}, pull requests in older commits are missing", stop.description());
+                    }
+                    break;
+                }
+                Outcome::Skipped => {}
+                Outcome::Patch(key) => {
+                    pr_commits.push(oid);
+                    match patchsets.iter_mut().find(|p| p.0 == key) {
+                        Some(series) => series.1.push(oid),
+                        None => patchsets.push((key, vec![oid]))
+                    }
+                }
+                Outcome::Squash(pr) => {
+                    pr_commits.push(oid);
+                    match config.empty_title_policy.apply(*pr, config) {
+                        Some(pr) => pull_requests.push(pr),
+                        None => summary.skip(SkipReason::EmptyTitle)
+                    }
+                }
+                Outcome::PlainMerge(commit) => {
+                    summary.merges += 1;
+                    plain_merges.push(commit);
+                }
+                Outcome::Octopus => {
+                    summary.merges += 1;
+                    summary.skip(SkipReason::Octopus);
+                    if let OctopusPolicy::Warn = config.octopus {
+                        warnings += 1;
+                    }
+                }
+                Outcome::Merge(Ok(prs)) => {
+                    summary.merges += 1;
+                    pr_commits.push(oid);
+                    for pr in prs {
+                        match config.empty_title_policy.apply(pr, config) {
+                            Some(pr) => pull_requests.push(pr),
+                            None => summary.skip(SkipReason::EmptyTitle)
+                        }
+                    }
+                }
+                Outcome::Merge(Err(e)) => {
+                    summary.merges += 1;
+                    invalid += 1;
+                    warnings += 1;
+                    summary.skip(e.reason);
+                    if !config.quiet {
+                        warn!("Error parsing commit: {}", e);
+                    }
+                }
+            }
+        }
+        if !patchsets.is_empty() {
+            // git am often applies a whole series within a second, so commit times can't
+            // tell the order of its patches
+            let order = merge_order(repo, &oids).map_err(|e| format!("cannot order patches: {}", e))?;
+            for (_, mut patches) in patchsets {
+                patches.sort_by_key(|oid| order.get(oid).cloned());
+                let pr = PullRequestInfo::from_patch_series(repo, &patches, config)
+                    .map_err(|e| format!("cannot read patch series: {}", e))?;
+                match config.empty_title_policy.apply(pr, config) {
+                    Some(pr) => pull_requests.push(pr),
+                    None => summary.skip(SkipReason::EmptyTitle)
+                }
+            }
+            pull_requests.sort_by_key(|pr| cmp::Reverse(pr.time));
+        }
+        summary.pull_requests = pull_requests.len();
+
+        let other_changes = if config.include_other_changes {
+            other_changes(repo, &oids, &pr_commits, config)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Collection {
+            oids,
+            pull_requests,
+            plain_merges,
+            direct_commits,
+            other_changes,
+            summary,
+            invalid,
+            warnings
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pull_request(id: u32, name: &str, branch: &str, sha: &str, time: i64) -> PullRequestInfo {
+        PullRequestInfo {
+            provider: Provider::GitHub,
+            id,
+            sha: sha.to_string(),
+            time,
+            author: "alice".to_string(),
+            branch: branch.to_string(),
+            name: name.to_string(),
+            body: String::new(),
+            commits: vec![],
+            first_release: None,
+            author_check_failed: false,
+            excerpt: None,
+            labels: vec![],
+            milestone: None,
+            merged_by: None,
+            author_association: None,
+            reviewers: None,
+            email: None,
+            reverted_by: None,
+            url: None,
+            category: None
+        }
+    }
+
+    #[test]
+    fn parses_merge_messages_of_the_given_provider() {
+        let header = "Merge pull request '#12' from alice/fix";
+        let auto = Config::default();
+        assert_eq!(parse_merge_message(header, "", &auto), Some(Ok((Provider::Gitea, 12, Some("alice".to_string()), "fix".to_string()))));
+        let github = Config { provider: Provider::GitHub, ..Config::default() };
+        assert_eq!(parse_merge_message(header, "", &github), None);
+        let custom = Config { header_patterns: vec![Regex::new(r"^Land #(?P<id>\d+) \((?P<branch>\S+)\)$").unwrap()], ..Config::default() };
+        assert_eq!(parse_merge_message("Land #5 (fix)", "", &custom), Some(Ok((Provider::GitHub, 5, None, "fix".to_string()))));
+    }
+
+    #[test]
+    fn parses_patchset_links() {
+        let message = "Add frobnicator\n\nLink: https://lists.sr.ht/~me/proj-devel/patches/4242\n";
+        assert_eq!(parse_patch_link(message),
+                   Some(("~me/proj-devel/patches/4242".to_string(), 4242, "https://lists.sr.ht/~me/proj-devel/patches/4242".to_string())));
+        assert_eq!(parse_patch_link("Add frobnicator\n\nSigned-off-by: A <a@x>\n"), None);
+    }
+
+    #[test]
+    fn groups_patch_emails_into_series() {
+        let first = "A\n\nLink: https://lists.sr.ht/~me/proj-devel/%3C20240101120000.111-1-a@x%3E\n";
+        let second = "B\n\nLink: https://lists.sr.ht/~me/proj-devel/<20240101120000.111-2-a@x>/\n";
+        let (key, id, url) = parse_patch_link(first).unwrap();
+        assert_eq!((&key[..], id), ("~me/proj-devel/20240101120000.111-a@x", 0));
+        assert_eq!(url, "https://lists.sr.ht/~me/proj-devel/%3C20240101120000.111-1-a@x%3E");
+        assert_eq!(parse_patch_link(second).unwrap().0, key);
+    }
+
+    #[test]
+    fn strips_patch_prefixes() {
+        assert_eq!(patch_title("[PATCH v2 1/3] Add frobnicator"), "Add frobnicator");
+        assert_eq!(patch_title("[PATCH] Add frobnicator"), "Add frobnicator");
+        assert_eq!(patch_title("[RFC PATCH proj] Add frobnicator"), "Add frobnicator");
+        assert_eq!(patch_title("Add [PATCH] handling"), "Add [PATCH] handling");
+    }
+
+    #[test]
+    fn finds_reverted_pull_requests() {
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        // Newest first, with #3 merged again after a revert
+        let prs = vec![pull_request(3, "Retry parsing", "retry", "c", 300), pull_request(5, "Cache results", "cache", sha, 200),
+                       pull_request(3, "Retry parsing", "retry", "a", 100)];
+        assert_eq!(revert_targets("Revert \"Retry parsing\"", 250, &prs), vec![2]);
+        assert_eq!(revert_targets("Revert \"Merge pull request #3 from alice/retry\" (#6)", 350, &prs), vec![0]);
+        assert_eq!(revert_targets("Revert retry\n\nReverts alice/project#3", 250, &prs), vec![2]);
+        assert_eq!(revert_targets("Reverts #5", 250, &prs), vec![1]);
+        assert_eq!(revert_targets(&format!("Revert \"Cache\"\n\nThis reverts commit {}.", sha), 250, &prs), vec![1]);
+        assert!(revert_targets("Revert \"Something else\"", 350, &prs).is_empty());
+    }
+
+    #[test]
+    fn parses_conventional_titles() {
+        assert_eq!(parse_conventional_title("feat(parser): support tabs"), Some(("feat".to_string(), false)));
+        assert_eq!(parse_conventional_title("Fix!: drop the old flag"), Some(("fix".to_string(), true)));
+        assert_eq!(parse_conventional_title("fix(cli)!: rename --out"), Some(("fix".to_string(), true)));
+        assert_eq!(parse_conventional_title("feat:no space"), None);
+        assert_eq!(parse_conventional_title("Support tabs in the parser"), None);
+    }
+
+    #[test]
+    fn categorizes_by_title_type_or_branch_prefix() {
+        assert_eq!(category_key(&pull_request(1, "feat(parser): support tabs", "x/tabs", "a", 0)), Some("feat".to_string()));
+        assert_eq!(category_key(&pull_request(1, "refactor!: split the parser", "x/split", "a", 0)), Some("breaking".to_string()));
+        assert_eq!(category_key(&pull_request(1, "Support tabs", "Fix/tabs", "a", 0)), Some("fix".to_string()));
+        assert_eq!(category_key(&pull_request(1, "Support tabs", "tabs", "a", 0)), None);
+
+        let mut prs = vec![pull_request(1, "docs: explain tabs", "tabs", "a", 0), pull_request(2, "chore: bump", "bump", "b", 0)];
+        categorize(&mut prs, &vec![("docs".to_string(), "Documentation".to_string())]);
+        assert_eq!(prs[0].category, Some("Documentation".to_string()));
+        assert_eq!(prs[1].category, None);
+    }
+
+    #[test]
+    fn splits_team_from_community_by_association() {
+        let mut pr = pull_request(1, "Support tabs", "tabs", "a", 0);
+        let mut origin = |association: Option<&str>| {
+            pr.author_association = association.map(|a| a.to_string());
+            GroupBy::Origin.keys(&pr)
+        };
+        assert_eq!(origin(Some("OWNER")), vec!["team"]);
+        assert_eq!(origin(Some("MEMBER")), vec!["team"]);
+        assert_eq!(origin(Some("COLLABORATOR")), vec!["community"]);
+        assert_eq!(origin(Some("CONTRIBUTOR")), vec!["community"]);
+        assert!(origin(None).is_empty());
+    }
+
+    #[test]
+    fn extracts_marked_deprecations() {
+        let mut marked = pull_request(1, "Rework the config loader", "config", "a", 0);
+        marked.body = "Some cleanup.\n\nDeprecated: the --conf flag, use --config\n".to_string();
+        let mut trailer = pull_request(2, "Drop the old API", "api", "b", 0);
+        trailer.body = "DEPRECATION: the v1 endpoints".to_string();
+        let mut labelled = pull_request(3, "Rename the option", "rename", "c", 0);
+        labelled.labels = vec!["Deprecation".to_string()];
+        let titled = pull_request(4, "Removed: the legacy parser", "legacy", "d", 0);
+        let mut prs = vec![marked, trailer, labelled, titled];
+
+        let deprecations = extract_deprecations(&mut prs);
+        assert!(prs.is_empty());
+        assert_eq!(deprecations.iter().map(|pr| pr.id).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(deprecations[0].excerpt.as_deref(), Some("the --conf flag, use --config"));
+        assert_eq!(deprecations[1].excerpt.as_deref(), Some("the v1 endpoints"));
+        assert_eq!(deprecations[2].excerpt, None);
+        assert_eq!(deprecations[3].excerpt, None);
+    }
+
+    #[test]
+    fn ignores_deprecations_mentioned_in_prose() {
+        let mut prose = pull_request(1, "Remove flicker when resizing", "flicker", "a", 0);
+        prose.body = "This removes a redundant redraw.\nThe deprecated renderer is untouched.".to_string();
+        let mut quoted = pull_request(2, "Fix the docs", "docs", "b", 0);
+        quoted.body = "Mention that `Removed:` lines are collected".to_string();
+        let mut prs = vec![prose, quoted];
+
+        assert!(extract_deprecations(&mut prs).is_empty());
+        assert_eq!(prs.len(), 2);
+    }
+
+    #[test]
+    fn cleans_template_boilerplate_from_bodies() {
+        let body = "<!-- Describe your change -->\n## Summary\n\nSupport tabs in tables.\n\n\n\
+                    <!--\nMultiline\ncomment\n-->\n### Checklist\n- [x] Tests\n* [ ] Docs\n\nCloses #3  \n";
+        assert_eq!(clean_body(body), "Support tabs in tables.\n\nCloses #3");
+        assert_eq!(clean_body("<!-- only the template -->\n## Summary\n- [ ] Tests\n"), "");
+        assert_eq!(clean_body("#42 is fixed"), "#42 is fixed");
+    }
+
+    #[test]
+    fn extracts_titles_from_bodies() {
+        let body = "\n  Support tabs\n  in tables  \n\nDetails follow.";
+        assert_eq!(TitleSource::Body.extract(body), body);
+        assert_eq!(TitleSource::FirstLine.extract(body), "Support tabs");
+        assert_eq!(TitleSource::FirstParagraph.extract(body), "Support tabs in tables");
+        assert_eq!(TitleSource::FirstLine.extract(""), "");
+        assert_eq!(TitleSource::FirstParagraph.extract("\n\n"), "");
+    }
+
+    #[test]
+    fn derives_titles_from_branch_names() {
+        let mut config = Config::default();
+        assert_eq!(branch_title("fix-table-width", &config), "fix table width");
+        assert_eq!(branch_title("feature/tabs_in-tables", &config), "feature/tabs_in tables");
+
+        config.humanize_branch = true;
+        config.branch_prefixes = vec!["feature/".to_string(), "fix/".to_string()];
+        assert_eq!(branch_title("feature/tabs_in-tables", &config), "Tabs in tables");
+        assert_eq!(branch_title("fix/parser/--nested", &config), "Parser nested");
+        assert_eq!(branch_title("feature/", &config), "Feature");
+        assert_eq!(branch_title("élan-vital", &config), "Élan vital");
+    }
+
+    #[test]
+    fn computes_edit_distances() {
+        assert_eq!(edit_distance("v1.2.0", "v1.2.0"), 0);
+        assert_eq!(edit_distance("v1.20", "v1.2.0"), 1);
+        assert_eq!(edit_distance("1.2.0", "v1.2.0"), 1);
+        assert_eq!(edit_distance("v2.1.0", "v1.2.0"), 2);
+        assert_eq!(edit_distance("", "v1"), 2);
+        assert_eq!(edit_distance("vé", "ve"), 1);
+    }
+
+    #[test]
+    fn suggests_similar_tags() {
+        let dir = std::env::temp_dir().join(format!("git-pull-requests-tags-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let signature = git2::Signature::now("alice", "alice@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        let oid = repo.commit(None, &signature, &signature, "Initial commit", &tree, &[]).unwrap();
+        let commit = repo.find_object(oid, None).unwrap();
+        for tag in ["v1.2.0", "v1.3.0", "v10.0.0-rc.1", "release-2024"] {
+            repo.tag_lightweight(tag, &commit, false).unwrap();
+        }
+
+        let typo = suggest_tags(&repo, "v1.20");
+        let unrelated = suggest_tags(&repo, "nightly");
+        let error = validate_range(&repo, "v1.2.O..v10.0.0-rc.1").unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(typo, vec!["v1.2.0", "v1.3.0"]);
+        assert!(unrelated.is_empty());
+        assert_eq!(error, "revision v1.2.O not found; did you mean v1.2.0 or v1.3.0?");
+    }
+
+    #[test]
+    fn aligns_table_columns_by_display_width() {
+        let mut wide = pull_request(1, "表の幅を修正", "x/width", "a", 0);
+        wide.author = "山田".to_string();
+        let mut combining = pull_request(12, "Cafe\u{301} menu", "x/menu", "b", 0);
+        combining.author = "Zoe\u{308}".to_string();
+        let config = Config::default();
+
+        assert_eq!(table(&[wide, combining], &config), "\
+| PR  | Author | Title        |
+|-----|--------|--------------|
+| #1  | 山田   | 表の幅を修正 |
+| #12 | Zoe\u{308}    | Cafe\u{301} menu    |");
+    }
+
+    #[test]
+    fn parses_dates_in_utc() {
+        // Dates are UTC whatever the local time zone is, a day covers all of it and a time just
+        // its second
+        let day: Date = "2024-03-01".parse().unwrap();
+        assert_eq!((day.start, day.end), (1709251200, 1709337600));
+        let second: Date = "2024-03-01T12:30:15".parse().unwrap();
+        assert_eq!((second.start, second.end), (1709296215, 1709296216));
+        let leap: Date = "2024-02-29".parse().unwrap();
+        assert_eq!(leap.end, day.start);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for invalid in ["", "2024-3-1", "2024-03-01 12:30:15", "2024-03-01T12:30", "2024-03-01T12:30:15Z",
+                        "2024-03-01T12:30:15+02:00", "01.03.2024", "2024-13-01", "2024-03-01T25:00:00", "yesterday"] {
+            assert!(invalid.parse::<Date>().is_err(), "{} should be rejected", invalid);
+        }
+        assert_eq!("2024-03-01Z".parse::<Date>().err().unwrap(),
+                   "invalid date 2024-03-01Z, expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS");
+    }
+
+    #[test]
+    fn puts_the_summary_into_json_output() {
+        let mut summary = Summary { commits: 10, merges: 3, pull_requests: 1, ..Summary::default() };
+        summary.skip(SkipReason::Octopus);
+        summary.skip(SkipReason::EmptyTitle);
+        summary.skip(SkipReason::Octopus);
+        let config = Config { normalize: true, ..Config::default() };
+        let document = OutputFormat::Json.format_document(&[pull_request(7, "Support tabs", "x/tabs", "a", 0)], &summary, &config);
+
+        let json: Value = serde_json::from_str(&document.unwrap()).unwrap();
+        assert_eq!(json["summary"], json!({
+            "commits": 10,
+            "merges": 3,
+            "pull_requests": 1,
+            "skipped": {"octopus": 2, "empty_title": 1}
+        }));
+        assert_eq!(json["pull_requests"][0]["id"], json!(7));
+        assert_eq!(json["pull_requests"][0]["sha"], json!("<sha>"));
+    }
+
+    #[test]
+    fn links_pull_requests_to_their_forge() {
+        let config = Config {
+            forges: forge::for_links(Provider::Auto, "git.example.com", "acme/widgets"),
+            link_base: Some("https://git.example.com/acme/widgets".to_string()),
+            ..Config::default()
+        };
+        let mut github = pull_request(7, "Support tabs", "x/tabs", "a", 0);
+        let mut gitea = github.clone();
+        gitea.provider = Provider::Gitea;
+        let mut bitbucket = github.clone();
+        bitbucket.provider = Provider::Bitbucket;
+        assert_eq!(config.pull_request_url(&github).as_deref(), Some("https://git.example.com/acme/widgets/pull/7"));
+        assert_eq!(config.pull_request_url(&gitea).as_deref(), Some("https://git.example.com/acme/widgets/pulls/7"));
+        // Self-hosted Bitbucket has no built-in forge
+        assert_eq!(config.pull_request_url(&bitbucket).as_deref(), Some("https://git.example.com/acme/widgets/pull-requests/7"));
+        github.url = Some("https://lists.example.com/7".to_string());
+        assert_eq!(config.pull_request_url(&github).as_deref(), Some("https://lists.example.com/7"));
+
+        assert_eq!(config.compare_url(&[gitea], "v1", "v2").as_deref(), Some("https://git.example.com/acme/widgets/compare/v1...v2"));
+        assert_eq!(config.compare_url(&[bitbucket], "v1", "v2").as_deref(),
+                   Some("https://git.example.com/acme/widgets/branches/compare/v2%0Dv1"));
+    }
+
+    #[test]
+    fn parses_provider_names() {
+        for provider in [Provider::GitHub, Provider::GitLab, Provider::Gitea, Provider::Bitbucket, Provider::SourceHut, Provider::Auto] {
+            assert_eq!(provider.name().parse::<Provider>(), Ok(provider));
+        }
+        assert_eq!("forgejo".parse::<Provider>(), Ok(Provider::Gitea));
+        assert!("gogs".parse::<Provider>().is_err());
+    }
+
+    #[test]
+    fn parses_squash_headers() {
+        assert_eq!(parse_squash_header("Support tabs (#12)"), Some(("Support tabs".to_string(), 12)));
+        assert_eq!(parse_squash_header("Support (#3) tabs (#12) "), Some(("Support (#3) tabs".to_string(), 12)));
+        assert_eq!(parse_squash_header("Support tabs (#12) in the parser"), None);
+        assert_eq!(parse_squash_header("Support tabs #12"), None);
+    }
+
+    #[test]
+    fn detects_squash_merges() {
+        let dir = std::env::temp_dir().join(format!("git-pull-requests-test-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let signature = git2::Signature::now("alice", "alice@example.com").unwrap();
+        let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        let commit = |message: &str, parents: &[&git2::Commit]| {
+            let oid = repo.commit(None, &signature, &signature, message, &tree, parents).unwrap();
+            repo.find_commit(oid).unwrap()
+        };
+
+        // A root commit can't have been squash merged
+        let root = commit("Initial commit (#1)", &[]);
+        let squash = commit("Support tabs (#12)\n\n* Parse tabs\n* Render tabs", &[&root]);
+        let plain = commit("Support tabs", &[&squash]);
+        let merge = commit("Merge branch 'tabs' (#13)", &[&plain, &squash]);
+        let results = [is_squash(&root), is_squash(&squash), is_squash(&plain), is_squash(&merge)];
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(results, [false, true, false, false]);
+    }
+}
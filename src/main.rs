@@ -1,194 +1,1427 @@
-#![feature(plugin)]
-#![plugin(docopt_macros, regex_macros)]
-
-extern crate docopt;
-extern crate rustc_serialize;
+extern crate clap;
 extern crate git2;
-extern crate regex;
-extern crate itertools;
 #[macro_use] extern crate log;
 extern crate fern;
+extern crate time;
+#[macro_use] extern crate serde_json;
+extern crate git_pull_requests;
 
 use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::process;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
 use std::fmt::Write;
 
-use rustc_serialize::{Decodable, Decoder};
-use itertools::Itertools;
+use clap::{Parser, Subcommand};
 use git2::Repository;
 
-docopt! { Args, r"
-Usage:
-  git-pull-requests [options] <commit-range>
-  git-pull-requests --help
-  git-pull-requests --version
-
-Options:
-  --skip-invalid      Skip invalid merge commits.
-  --repo-name <repo>  Set repository name to be used in output.
-  --format <format>   Set output format [default: markdown]
-  --omit-author       Do not print commit author names.
-  --help, -h          Show this message.
-  --version           Show application version.
-", flag_repo_name: Option<String>, flag_format: OutputFormat }
-
-const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
-
-struct Config {
-    output_format: OutputFormat,
+use git_pull_requests::*;
+use git_pull_requests::error::Error;
+
+#[derive(Parser)]
+#[command(name = "git-pull-requests", version, about = "Lists pull requests merged in a range of commits")]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(after_help = "Exit codes:
+  2    invalid arguments or configuration
+  3    the repository cannot be opened, fetched or read
+  4    the range cannot be resolved or walked
+  5    commits cannot be parsed
+  6    files or commands cannot be read, written or run
+  7    no pull requests were found and --fail-if-empty was given
+  124  --timeout reached
+  130  interrupted")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
+    /// Ranges of commits to list pull requests from, e.g. v1.0..v1.1; several ranges are
+    /// walked separately and combined.
+    #[arg(value_name = "COMMIT_RANGE")]
+    commit_ranges: Vec<String>,
+
+    /// Use the range from the tag preceding the given one to the given tag.
+    #[arg(long, value_name = "tag", global = true)]
+    release: Option<String>,
+    /// Use the range covering the last n tagged releases.
+    #[arg(long, value_name = "n", global = true)]
+    last_n_releases: Option<usize>,
+    /// Use the whole history up to the most recent tag reachable from HEAD, with one section
+    /// per tag, newest first.
+    #[arg(long, global = true)]
+    all_releases: bool,
+    /// Use the range from the most recent tag reachable from the --to revision to that revision.
+    #[arg(long, global = true)]
+    since_last_tag: bool,
+    /// Use the range from the given tag to the --to revision.
+    #[arg(long, value_name = "tag", global = true)]
+    from_tag: Option<String>,
+    /// End of the range for --since-last-tag and --from-tag.
+    #[arg(long, value_name = "rev", default_value = "HEAD", global = true)]
+    to: String,
+    /// Skip commits reachable from the given ref, e.g. pull requests already released from
+    /// another branch.
+    #[arg(long, value_name = "ref", global = true)]
+    exclude_reachable_from: Vec<String>,
+    /// Only list pull requests marked for backporting which are not yet on the given branch.
+    #[arg(long, value_name = "branch", global = true)]
+    backport_candidates: Option<String>,
+    /// Leave out pull requests with the given label, see --enrich; replaces the
+    /// exclude-labels setting.
+    #[arg(long, value_name = "label", global = true)]
+    exclude_label: Vec<String>,
+    /// Label which marks pull requests for backporting, see --enrich; pull requests without
+    /// labels are marked by this text in their title or description.
+    #[arg(long, value_name = "text", default_value = "backport-needed", global = true)]
+    backport_marker: String,
+    /// Fetch the remote before resolving the range. HTTPS remotes use a token from
+    /// GIT_PULL_REQUESTS_TOKEN or GITHUB_TOKEN or git credential helpers, SSH remotes use
+    /// the SSH agent.
+    #[arg(long, global = true)]
+    fetch: bool,
+    /// Remote to fetch.
+    #[arg(long, value_name = "name", default_value = "origin", global = true)]
+    remote: String,
+    /// Analyze the repository at the given URL instead of the current one, using a cached
+    /// mirror of it.
+    #[arg(long, value_name = "url", global = true)]
+    clone: Option<String>,
+    /// Analyze the repository containing the given path, which may be a working tree,
+    /// a .git directory or a bare repository, instead of the current directory.
+    #[arg(short = 'C', long = "repo", value_name = "path", global = true, conflicts_with = "clone")]
+    repo: Option<PathBuf>,
+    /// Read settings from the given file instead of the global config file and the
+    /// repository's .git-pull-requests.toml.
+    #[arg(long, value_name = "file", global = true)]
+    config: Option<PathBuf>,
+    /// Use the settings of the given [profile.NAME] table of the config files, which override
+    /// the other settings.
+    #[arg(long, value_name = "name", global = true)]
+    profile: Option<String>,
+    /// Override a setting for this run, e.g. --set omit-author=true or
+    /// --set profile.public.format=json; the value is read as in the config files, or as a
+    /// string if it isn't valid there. Can be repeated.
+    #[arg(long, value_name = "key=value", global = true)]
+    set: Vec<String>,
+    /// Write the output of every profile to the output file or changelog it sets, walking the
+    /// history and fetching details of pull requests only once; parsing follows the settings
+    /// outside of profiles.
+    #[arg(long, conflicts_with_all = ["profile", "template", "changelog", "output"])]
+    all_profiles: bool,
+    /// Skip invalid merge commits.
+    #[arg(long, global = true)]
+    skip_invalid: bool,
+    /// Fail if any commit couldn't be parsed or was skipped with a warning, even with
+    /// --skip-invalid or --max-invalid-ratio.
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Fail with exit code 7 if no pull requests were found, instead of warning.
+    #[arg(long, global = true, conflicts_with = "allow_empty")]
+    fail_if_empty: bool,
+    /// Produce a "No user-facing changes" document without a warning if no pull requests
+    /// were found.
+    #[arg(long, global = true)]
+    allow_empty: bool,
+    /// Set repository name to be used in output.
+    #[arg(long, value_name = "repo", global = true)]
     repo_name: Option<String>,
-    omit_author: bool
+    /// Web URL of the repository used for pull request links in markdown output; by default
+    /// it is derived from the remote URL.
+    #[arg(long, value_name = "url", global = true)]
+    link_base: Option<String>,
+    /// Hosting provider whose merge messages to recognize: github, gitlab, gitea (also for
+    /// Forgejo), bitbucket, sourcehut (only patch series, see --patchsets) or auto.
+    #[arg(long, value_name = "name", default_value = "auto", global = true)]
+    provider: Provider,
+    /// Set output format: markdown, table, json, cherry-pick-script, microblog or summary;
+    /// markdown unless set in a config file.
+    #[arg(long, value_name = "format", global = true)]
+    format: Option<OutputFormat>,
+    /// Link to include in microblog announcements.
+    #[arg(long, value_name = "url", global = true)]
+    announce_url: Option<String>,
+    /// Maximum length of a microblog post; longer announcements are split into a thread.
+    #[arg(long, value_name = "n", default_value = "500", global = true)]
+    max_post_length: usize,
+    /// Markdown heading level of section titles, from 1 to 6, so that the output can be
+    /// nested into an existing document.
+    #[arg(long, value_name = "n", default_value = "3", value_parser = clap::value_parser!(u8).range(1..=6),
+          global = true)]
+    heading_level: u8,
+    /// Set off markdown section titles as headings or as bold labels.
+    #[arg(long, value_name = "style", default_value = "heading", global = true)]
+    section_style: SectionStyle,
+    /// Do not print commit author names.
+    #[arg(long, global = true, overrides_with = "no_omit_author")]
+    omit_author: bool,
+    /// Print commit author names even if a config file sets omit-author.
+    #[arg(long, global = true, overrides_with = "omit_author")]
+    no_omit_author: bool,
+    /// Set what to do with pull requests with empty description: branch, api (fetch the title
+    /// from the forge, falling back to the branch), skip or blank.
+    #[arg(long, value_name = "policy", default_value = "branch", global = true)]
+    empty_title_policy: EmptyTitlePolicy,
+    /// Turn branch names used as titles into sentences, e.g. "fix/null-deref" into
+    /// "Fix null deref".
+    #[arg(long, global = true)]
+    humanize_branch: bool,
+    /// Comma-separated list of prefixes to remove from branch names used as titles,
+    /// e.g. "feature/,fix/".
+    #[arg(long, value_name = "prefixes", global = true)]
+    strip_branch_prefix: Option<String>,
+    /// Which part of the merge commit body to use as the title: body, first-line or
+    /// first-paragraph.
+    #[arg(long, value_name = "source", default_value = "body", global = true)]
+    title_from: TitleSource,
+    /// Show the SHA of each merge commit, abbreviated by default or with --show-sha=full
+    /// in full.
+    #[arg(long, value_name = "style", num_args = 0..=1, require_equals = true,
+          default_missing_value = "short", global = true)]
+    show_sha: Option<ShaStyle>,
+    /// Sort pull requests by pr-number, date or author, ascending, instead of listing them
+    /// newest first.
+    #[arg(long, value_name = "key", global = true)]
+    sort: Option<SortKey>,
+    /// Reverse the order of pull requests, after --sort if given. Neither applies to
+    /// cherry-pick scripts, which always follow the order of the merges.
+    #[arg(long, global = true)]
+    reverse: bool,
+    /// What to do with pull requests reverted in the same range, by a revert commit or
+    /// another pull request: drop both, annotate the reverted one or ignore the revert.
+    #[arg(long, value_name = "policy", default_value = "ignore", global = true)]
+    handle_reverts: RevertPolicy,
+    /// What to do with octopus merges (more than two parents): expand, warn or skip.
+    #[arg(long, value_name = "policy", default_value = "warn", global = true)]
+    octopus: OctopusPolicy,
+    /// Also recognize squash-merged pull requests, i.e. non-merge commits titled
+    /// "Some title (#123)".
+    #[arg(long, global = true)]
+    include_squash: bool,
+    /// Also recognize patch series applied from lists.sr.ht, i.e. non-merge commits with a
+    /// "Link: https://lists.sr.ht/..." trailer, and list each series as one entry.
+    #[arg(long, global = true)]
+    patchsets: bool,
+    /// Also list merge commits which are not pull request merges, using their subject line.
+    #[arg(long, global = true)]
+    include_plain_merges: bool,
+    /// List non-merge commits made directly on the target branch in a separate section.
+    #[arg(long, global = true)]
+    include_direct_commits: bool,
+    /// List non-merge commits which were not brought in by any pull request, such as commits
+    /// pushed directly or merged without a pull request, under "Other changes"; unlike the
+    /// "Direct commits" section, this also covers commits off the first-parent line.
+    #[arg(long, alias = "include-direct", global = true, conflicts_with = "include_direct_commits")]
+    include_other_changes: bool,
+    /// Follow only the first parent of merge commits, like git log --first-parent, so that
+    /// merges into branches which were merged later are not listed.
+    #[arg(long, global = true)]
+    first_parent: bool,
+    /// Only list pull requests merged on or after the given date, YYYY-MM-DD or
+    /// YYYY-MM-DDTHH:MM:SS in UTC, by committer time.
+    #[arg(long, value_name = "date", global = true)]
+    since: Option<Date>,
+    /// Only list pull requests merged on or before the given date, YYYY-MM-DD or
+    /// YYYY-MM-DDTHH:MM:SS in UTC, by committer time.
+    #[arg(long, value_name = "date", global = true)]
+    until: Option<Date>,
+    /// Number of threads used for parsing commits, computing diffs and, unless
+    /// --api-concurrency is given, for API requests.
+    #[arg(long, value_name = "n", default_value = "1", global = true)]
+    jobs: usize,
+    /// Number of concurrent API requests.
+    #[arg(long, value_name = "n", global = true)]
+    api_concurrency: Option<usize>,
+    /// Take titles, labels, milestones and merging users of pull requests from the API of
+    /// GitHub, GitLab, Gitea, Forgejo or Bitbucket; the forge and the repository are determined from the
+    /// remote URL. GitHub is accessed as a GitHub App installation when
+    /// GIT_PULL_REQUESTS_GITHUB_APP_ID and GIT_PULL_REQUESTS_GITHUB_APP_KEY (the path of the
+    /// app's private key) are set, optionally with GIT_PULL_REQUESTS_GITHUB_APP_INSTALLATION.
+    #[arg(long, global = true)]
+    enrich: bool,
+    /// Continue an interrupted or rate limited --enrich run, fetching details only for the
+    /// pull requests it didn't get to.
+    #[arg(long, global = true, requires = "enrich")]
+    resume: bool,
+    /// Save the responses of API requests into the given directory, for later use with
+    /// --replay.
+    #[arg(long, value_name = "dir", global = true, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer API requests from responses saved with --record in the given directory instead
+    /// of the network; requests which were not recorded fail.
+    #[arg(long, value_name = "dir", global = true)]
+    replay: Option<PathBuf>,
+    /// List the commits of each pull request under it; JSON output always includes them.
+    #[arg(long, global = true)]
+    show_commits: bool,
+    /// Split the output into sections by the release tags within the range.
+    #[arg(long, global = true)]
+    split_by_tag: bool,
+    /// Split the output into one section per commit range, in the order the ranges are given.
+    #[arg(long, global = true, conflicts_with_all = ["split_by_tag", "all_releases"])]
+    split_by_range: bool,
+    /// Shell command run for every author with the author name as $1; a non-zero exit status
+    /// marks the author's pull requests as failing the check, e.g. missing CLA.
+    #[arg(long, value_name = "cmd", global = true)]
+    author_check_cmd: Option<String>,
+    /// File mapping authors to organizations, one `author = organization` per line. Also used
+    /// by stats to count pull requests per organization.
+    #[arg(long, value_name = "file", global = true)]
+    org_map: Option<String>,
+    /// Add a section acknowledging organizations whose members contributed, according to
+    /// --org-map.
+    #[arg(long, global = true)]
+    show_sponsors: bool,
+    /// Move breaking changes into a separate section at the top: pull requests with a title
+    /// like "feat!: ...", a "BREAKING CHANGE:" footer or a breaking label (with --enrich).
+    #[arg(long, global = true)]
+    breaking_changes: bool,
+    /// Move pull requests marked as deprecations or removals, by a line like "Deprecated: ..." or
+    /// "Removed: ..." in the description or a deprecation label (with --enrich), into a separate
+    /// section at the top.
+    #[arg(long, global = true)]
+    deprecations: bool,
+    /// Summarize feature flags added or removed by pull requests, using the extraction rules
+    /// in the given file; each line is `added` or `removed`, then `diff` or `body`, then
+    /// a regex capturing the flag name.
+    #[arg(long, value_name = "file", global = true)]
+    feature_flags: Option<String>,
+    /// Split the output into sections like Features or Bug fixes by pull request label
+    /// (requires --enrich), branch-prefix, origin (team changes by owners and members of the
+    /// organization and community contributions by everybody else, requires --enrich),
+    /// category (by conventional commit type like feat: or fix: in the title, or else the
+    /// branch prefix) or none; none unless set in a config file.
+    #[arg(long, value_name = "key", global = true)]
+    group_by: Option<GroupBy>,
+    /// Map labels or branch prefixes to section titles with `label-or-prefix = title` lines
+    /// instead of using the built-in titles.
+    #[arg(long, value_name = "file", global = true)]
+    group_titles: Option<String>,
+    /// Split the output into pull requests whose commits all have a signature which
+    /// git verify-commit accepts and a Signed-off-by trailer, and all other pull
+    /// requests. Squash merges and patches are checked themselves.
+    #[arg(long, global = true)]
+    compliance: bool,
+    /// Add the files and directories changed by the most pull requests to stats.
+    #[arg(long, global = true)]
+    paths: bool,
+    /// Show the earliest tag containing each pull request.
+    #[arg(long, global = true)]
+    show_first_release: bool,
+    /// Fetch the users who approved each pull request and show them as "(approved by A, B)";
+    /// JSON output lists them as reviewers.
+    #[arg(long, global = true, requires = "enrich")]
+    show_reviewers: bool,
+    /// Show the date each pull request was merged, by the committer time of its merge commit.
+    #[arg(long, global = true)]
+    show_date: bool,
+    /// strftime format of the dates shown with --show-date, in UTC.
+    #[arg(long, value_name = "format", default_value = "%Y-%m-%d", global = true, requires = "show_date")]
+    date_format: String,
+    /// Replace SHAs and dates in the output with <sha> and <date> placeholders, so that
+    /// generated notes can be compared against snapshots.
+    #[arg(long, global = true)]
+    normalize: bool,
+    /// Include the e-mail of the pull request author, taken from the head commit of the merged
+    /// branch, in JSON and template output.
+    #[arg(long, global = true)]
+    show_email: bool,
+    /// Do not print warnings about individual commits, only the final summary.
+    #[arg(long, short, global = true)]
+    quiet: bool,
+    /// Skip invalid merge commits as long as they make up no more than the given percentage
+    /// of all merge commits.
+    #[arg(long, value_name = "pct", global = true)]
+    max_invalid_ratio: Option<f64>,
+    /// If the output is longer than this, replace the lists of entries with pull request
+    /// numbers or counts.
+    #[arg(long, value_name = "chars", global = true)]
+    max_length: Option<usize>,
+    /// Render the pull requests through the given Handlebars template instead of the selected
+    /// format; it gets `repo` and a `pull_requests` list with id, author, branch, title, sha
+    /// and date of each entry.
+    #[arg(long, value_name = "file", global = true)]
+    template: Option<String>,
+    /// Insert the output as a new release section at the top of the given changelog instead
+    /// of printing it; a section of the same release is replaced.
+    #[arg(long, value_name = "file", global = true)]
+    changelog: Option<String>,
+    /// Write the output to the given file instead of printing it.
+    #[arg(long, value_name = "file", global = true, conflicts_with = "changelog")]
+    output: Option<String>,
+    /// Name of the release used by --changelog and other release announcements; by default
+    /// it is the end of the commit range if that is a tag, and "Unreleased" otherwise.
+    #[arg(long, value_name = "name", global = true)]
+    release_name: Option<String>,
+    /// Date of the release used by --changelog; today by default.
+    #[arg(long, value_name = "date", global = true)]
+    release_date: Option<String>,
+    /// Stop collecting and enriching pull requests after the given number of seconds and
+    /// print what was found so far, exiting with code 124.
+    #[arg(long, value_name = "secs", global = true)]
+    timeout: Option<u64>,
+    /// Write the executed phases and their timings as JSON to the given file. Nothing is sent
+    /// anywhere.
+    #[arg(long, value_name = "file", global = true)]
+    run_report: Option<String>,
+    /// Shell command which every rendered section is piped through, e.g. to translate it;
+    /// its output replaces the section.
+    #[arg(long, value_name = "cmd", global = true)]
+    translate_cmd: Option<String>
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Print pull request counts per author and organization.
+    Stats {
+        #[arg(value_name = "COMMIT_RANGE")]
+        commit_ranges: Vec<String>
+    },
+    /// Print commit, merge and pull request counts of the range without parsing it fully.
+    Preview {
+        commit_range: String
+    },
+    /// Find the pull request which brought a revision or a <path>:<line> into HEAD.
+    WhichPr {
+        target: String
+    },
+    /// Find the merge commit of a pull request.
+    Find {
+        pr_id: u32,
+        commit_range: Option<String>
+    },
+    /// List pull requests merged into only one of two branches.
+    Divergence {
+        branch_a: String,
+        branch_b: String
+    },
+    /// Cherry-pick pull requests of the ranges onto a branch in the order they were merged,
+    /// choosing them from a numbered list or with --pick; with --backport-candidates only those
+    /// not yet on the branch are listed. The branch is left unchanged if any of them conflicts.
+    Backport {
+        /// Branch to cherry-pick the pull requests onto.
+        branch: String,
+        #[arg(value_name = "COMMIT_RANGE")]
+        commit_ranges: Vec<String>,
+        /// Cherry-pick the pull request with the given id instead of choosing from the list;
+        /// can be repeated.
+        #[arg(long, value_name = "id")]
+        pick: Vec<u32>,
+        /// Check that the pull requests apply cleanly without changing the branch.
+        #[arg(long)]
+        dry_run: bool
+    },
+    /// Manage cached mirrors of repositories analyzed with --clone.
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction
+    },
+    /// Manage tokens for hosting providers.
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction
+    },
+    /// Check or upgrade configuration files.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction
+    },
+    /// Print the directories used for configuration, cache and state.
+    Paths,
+    /// Replace the running executable with the binary of the latest release of this tool for
+    /// this platform, after checking it against the SHA256SUMS published with the release.
+    SelfUpdate {
+        /// Only print whether a newer release is available.
+        #[arg(long)]
+        check: bool,
+        /// Take the releases from the given host, e.g. a GitHub Enterprise mirror of the project.
+        #[arg(long, value_name = "host", default_value = "github.com")]
+        host: String
+    },
+    /// Publish a release of the given version: add the notes to the changelog, create a signed
+    /// tag containing them, push it, publish a GitHub release and send the notifications,
+    /// skipping the steps which are turned off.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Release {
+        #[command(subcommand)]
+        action: Option<ReleaseAction>,
+        #[command(flatten)]
+        steps: Box<ReleaseSteps>
+    }
+}
+
+#[derive(clap::Args)]
+struct ReleaseSteps {
+    /// Version to release, used as the tag and release name.
+    #[arg(required = true)]
+    version: Option<String>,
+    /// Ranges covered by the release, by default everything since the most recent tag
+    /// reachable from the --to revision.
+    #[arg(value_name = "COMMIT_RANGE")]
+    commit_ranges: Vec<String>,
+    /// Sign the tag with the given key instead of the default one.
+    #[arg(long, value_name = "key")]
+    local_user: Option<String>,
+    /// Don't add the notes to the changelog given by --changelog, or CHANGELOG.md in the
+    /// working tree; the changelog is not committed.
+    #[arg(long)]
+    no_changelog: bool,
+    /// Don't create the tag, e.g. because it already exists.
+    #[arg(long)]
+    no_tag: bool,
+    /// Append a Release-PRs trailer listing the ids of the released pull requests, e.g.
+    /// "Release-PRs: 123,124,130", to the tag message.
+    #[arg(long)]
+    pr_trailer: bool,
+    /// Don't push the tag to the remote given by --remote.
+    #[arg(long)]
+    no_push: bool,
+    /// Don't publish a GitHub release.
+    #[arg(long)]
+    no_github_release: bool,
+    /// Close the open GitHub milestone with the given title.
+    #[arg(long, value_name = "title")]
+    close_milestone: Option<String>,
+    /// Comment on the GitHub issues which the released pull requests close, as in "Fixes #12",
+    /// with the given text; {version} and {url} are replaced with the version and the URL of
+    /// the release.
+    #[arg(long, value_name = "text")]
+    comment_on_fixed_issues: Option<String>,
+    /// Add the given label to the released pull requests on GitHub.
+    #[arg(long, value_name = "label")]
+    label_released: Option<String>,
+    /// Post the notes as a GitHub discussion in the given category, e.g. Announcements.
+    #[arg(long, value_name = "category")]
+    post_discussion: Option<String>,
+    /// Shell command run at the end with the version and the release URL as $1 and $2 and
+    /// the notes on stdin, e.g. to post an announcement.
+    #[arg(long, value_name = "cmd")]
+    notify_cmd: Option<String>,
+    /// Post the notes to the given target of the notify settings, a Matrix room or a
+    /// Mattermost or Teams webhook configured in a [notify.NAME] table; can be repeated.
+    #[arg(long, value_name = "name")]
+    notify: Vec<String>,
+    /// Print the steps which would run and the notes without changing anything; the
+    /// pre-publish and post-publish hooks are skipped.
+    #[arg(long)]
+    dry_run: bool
+}
+
+#[derive(Subcommand)]
+enum ReleaseAction {
+    /// Create a GPG-signed annotated tag for the given version containing the release notes,
+    /// which cover the given ranges or, by default, everything since the most recent tag
+    /// reachable from the --to revision; the tag points to the end of the last range.
+    Tag {
+        version: String,
+        #[arg(value_name = "COMMIT_RANGE")]
+        commit_ranges: Vec<String>,
+        /// Sign the tag with the given key instead of the default one.
+        #[arg(long, value_name = "key")]
+        local_user: Option<String>,
+        /// Push the tag to the remote given by --remote.
+        #[arg(long)]
+        push: bool,
+        /// Append a Release-PRs trailer listing the ids of the released pull requests, e.g.
+        /// "Release-PRs: 123,124,130", to the tag message.
+        #[arg(long)]
+        pr_trailer: bool
+    }
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    List,
+    Clear,
+    Gc {
+        /// Remove cached mirrors not used for the given number of days.
+        #[arg(long, value_name = "days", default_value = "30")]
+        max_age: u64,
+        /// Remove least recently used cached mirrors until the cache is smaller than the given
+        /// size.
+        #[arg(long, value_name = "mb")]
+        max_size: Option<u64>
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Check the configuration files which would be read for unknown keys and invalid values
+    /// and print their paths.
+    Validate,
+    /// Rename keys of the configuration files which would be read from the names used by
+    /// earlier versions, keeping each original file next to it with a .bak extension.
+    Migrate
+}
+
+#[derive(Subcommand)]
+enum AuthAction {
+    Login {
+        /// Host to store the token for.
+        #[arg(long, default_value = "github.com")]
+        host: String
+    },
+    Status {
+        /// Host to check credentials for.
+        #[arg(long, default_value = "github.com")]
+        host: String
+    }
 }
 
 macro_rules! try_error {
-    ($e:expr, $ei:ident => $($args:tt)*) => {
+    ($e:expr, $kind:ident, $ei:ident => $($args:tt)*) => {
         match $e {
             Ok(r) => r,
-            Err($ei) => {
-                error!($($args)*);
-                return;
-            }
+            Err($ei) => return Err(Error::$kind(format!($($args)*)))
         }
     }
 }
 
-#[derive(Copy, Clone)]
-enum OutputFormat {
-    Markdown
+fn remote_repository(repo: &Repository, args: &Args) -> Option<(String, String)> {
+    let url = match args.clone {
+        Some(ref url) => Some(url.clone()),
+        None => repo.find_remote(&args.remote).ok().and_then(|r| r.url().map(|u| u.to_string()))
+    };
+    url.as_ref().and_then(|u| github::repository_of_url(u))
 }
 
-impl Decodable for OutputFormat {
-    fn decode<D: Decoder>(d: &mut D) -> Result<OutputFormat, D::Error> {
-        d.read_str().and_then(|s| match &s[..] {
-            "markdown" => Ok(OutputFormat::Markdown),
-            s => Err(d.error(&format!("unknown format: {}", s)))
-        })
+// Version and ranges of the release subcommands
+fn release_version(args: &Args) -> Option<(&String, &Vec<String>)> {
+    match args.command {
+        Some(Cmd::Release { action: Some(ReleaseAction::Tag { ref version, ref commit_ranges, .. }), .. }) =>
+            Some((version, commit_ranges)),
+        Some(Cmd::Release { action: None, ref steps }) => steps.version.as_ref().map(|v| (v, &steps.commit_ranges)),
+        _ => None
     }
 }
 
-impl OutputFormat {
-    fn format(self, info: &PullRequestInfo, config: &Config) -> String {
-        match self {
-            OutputFormat::Markdown => {
-                let mut r: String = " * ".into();
-                if let Some(ref repo) = config.repo_name {
-                    r.push_str(repo);
-                }
-                write!(&mut r, "#{} ", info.id).unwrap();
-                if !config.omit_author {
-                    write!(&mut r, "(by {}) ", info.author).unwrap();
-                }
-                write!(&mut r, "- {}", info.name).unwrap();
-                r
+// Tags point to the end of the last range
+fn release_target<'a>(args: &'a Args, commit_ranges: &'a [String]) -> &'a str {
+    commit_ranges.last().and_then(|r| r.rsplit("..").next()).unwrap_or(&args.to)
+}
+
+fn release_date(args: &Args) -> String {
+    match args.release_date {
+        Some(ref date) => date.clone(),
+        None if args.normalize => "<date>".into(),
+        None => format_tm(time::now(), "%Y-%m-%d")
+    }
+}
+
+// The tag a range starts at, or the latest tag before its start; ranges without a start cover
+// the whole history, so there is no previous version
+fn previous_version(repo: &Repository, range: &str) -> Option<String> {
+    let start = range.split_once("..").map(|(start, _)| start).filter(|s| !s.is_empty())?;
+    if release::tag_exists(repo, start) {
+        Some(start.to_string())
+    } else {
+        latest_tag(repo, start).ok()
+    }
+}
+
+fn template_document(args: &Args, repo: &Repository, config: &Config, ranges: &[String],
+                     pull_requests: &[PullRequestInfo]) -> template::Document {
+    let last = ranges.last().map_or("", |r| &r[..]);
+    let previous = previous_version(repo, last);
+    let end = match config.release_name {
+        Some(ref name) if release::tag_exists(repo, name) => name.clone(),
+        _ => last.rsplit("..").next().unwrap_or(last).to_string()
+    };
+    let compare_url = previous.as_ref().and_then(|previous| config.compare_url(pull_requests, previous, &end));
+    template::Document {
+        repo_name: config.repo_name.clone(),
+        version: config.release_name.clone(),
+        previous_version: previous,
+        release_date: release_date(args),
+        compare_url,
+        range: ranges.join(" ")
+    }
+}
+
+// Everything which can be checked is checked before the first step, so that a misconfigured
+// release doesn't stop halfway
+fn publish(args: &Args, repo: &Repository, destination: &Destination, steps: &ReleaseSteps, version: &str, text: &str,
+           pull_requests: &[PullRequestInfo]) -> Result<(), String> {
+    let released = released_ids(pull_requests);
+    let changelog = if steps.no_changelog {
+        None
+    } else {
+        Some(match destination.changelog {
+            Some(ref path) => PathBuf::from(path),
+            None => repo.workdir().map(|d| d.join("CHANGELOG.md"))
+                .ok_or_else(|| "bare repositories have no CHANGELOG.md, use --changelog or --no-changelog".to_string())?
+        })
+    };
+    let uses_github = steps.close_milestone.is_some() || steps.comment_on_fixed_issues.is_some()
+        || steps.label_released.is_some() || steps.post_discussion.is_some();
+    let github = if steps.no_github_release && !uses_github {
+        None
+    } else {
+        Some(remote_repository(repo, args).ok_or_else(|| if steps.no_github_release {
+            format!("cannot determine the GitHub repository from remote {}", args.remote)
+        } else {
+            format!("cannot determine the GitHub repository from remote {}, use --no-github-release", args.remote)
+        })?)
+    };
+    let fixed_issues = if steps.comment_on_fixed_issues.is_some() { github::fixed_issues(pull_requests) } else { Vec::new() };
+    let notify_targets = steps.notify.iter().map(|name| {
+        destination.notify.get(name).map(|target| (name, target)).ok_or_else(|| format!("unknown notification target {}", name))
+    }).collect::<Result<Vec<_>, _>>()?;
+    let target = release_target(args, &steps.commit_ranges);
+
+    let mut plan = Vec::new();
+    if let Some(ref path) = changelog {
+        plan.push(format!("add the notes to {}", path.display()));
+    }
+    if !steps.no_tag {
+        plan.push(format!("create signed tag {} at {}", version, target));
+    }
+    if !steps.no_push {
+        plan.push(format!("push tag {} to {}", version, args.remote));
+    }
+    if let Some((ref host, ref repository)) = github {
+        if !steps.no_github_release {
+            plan.push(format!("publish release {} of {} on {}", version, repository, host));
+        }
+        if let Some(ref milestone) = steps.close_milestone {
+            plan.push(format!("close milestone {} of {}", milestone, repository));
+        }
+        if steps.comment_on_fixed_issues.is_some() && !fixed_issues.is_empty() {
+            plan.push(format!("comment on fixed issues {}", fixed_issues.iter().map(|id| format!("#{}", id)).collect::<Vec<_>>().join(", ")));
+        }
+        if let Some(ref label) = steps.label_released {
+            plan.push(format!("add label {} to {} pull requests", label, released.len()));
+        }
+        if let Some(ref category) = steps.post_discussion {
+            plan.push(format!("post the notes as a discussion in {} of {}", category, repository));
+        }
+    }
+    for &(name, target) in &notify_targets {
+        plan.push(format!("post the notes to {} on {}", name, target.kind.name()));
+    }
+    if let Some(ref cmd) = steps.notify_cmd {
+        plan.push(format!("run {}", cmd));
+    }
+    if steps.dry_run {
+        println!("Release {} would:", version);
+        for step in &plan {
+            println!("  {}", step);
+        }
+        println!();
+        print!("{}", text);
+        return Ok(());
+    }
+
+    // The milestone and the discussion category are looked up first, as they may not exist
+    let client = match github {
+        Some((ref host, ref repository)) => Some(github::authenticate(host, repository)?),
+        None => None
+    };
+    let milestone = match (client.as_ref(), steps.close_milestone.as_ref()) {
+        (Some(client), Some(title)) => Some(client.open_milestone(title)?),
+        _ => None
+    };
+    let category = match (client.as_ref(), steps.post_discussion.as_ref()) {
+        (Some(client), Some(name)) => Some(client.discussion_category(name)?),
+        _ => None
+    };
+
+    if let Some(ref path) = changelog {
+        changelog::insert(path, version, &release_date(args), text)?;
+    }
+    if !steps.no_tag {
+        release::create_tag(repo, version, target, text, steps.local_user.as_ref().map(|k| &k[..]),
+                            &tag_trailers(steps.pr_trailer, &released))?;
+    }
+    if !steps.no_push {
+        release::push_tag(repo, &args.remote, version)?;
+    }
+    let url = match client {
+        Some(ref client) if !steps.no_github_release => client.create_release(version, text)?,
+        _ => String::new()
+    };
+    if let Some(ref client) = client {
+        if let Some(number) = milestone {
+            client.close_milestone(number)?;
+        }
+        if let Some(ref template) = steps.comment_on_fixed_issues {
+            let comment = template.replace("{version}", version).replace("{url}", &url);
+            for &issue in &fixed_issues {
+                client.comment(issue, &comment)?;
             }
         }
+        if let Some(ref label) = steps.label_released {
+            for &id in &released {
+                client.add_label(id, label)?;
+            }
+        }
+        if let Some(ref category) = category {
+            let discussion = client.create_discussion(category, version, text)?;
+            eprintln!("Posted discussion: {}", discussion);
+        }
     }
+    for &(name, target) in &notify_targets {
+        target.send(version, &url, text).map_err(|e| format!("cannot notify {}: {}", name, e))?;
+    }
+    if let Some(ref cmd) = steps.notify_cmd {
+        release::notify(cmd, version, &url, text)?;
+    }
+    eprintln!("Released {}{}", version, if url.is_empty() { String::new() } else { format!(": {}", url) });
+    Ok(())
 }
 
-#[derive(Clone, Debug)]
-struct PullRequestInfo {
-    id: u32,
-    author: String,
-    branch: String,
-    name: String
+// Ids of the pull requests covered by the release, for the Release-PRs tag trailer
+fn released_ids(pull_requests: &[PullRequestInfo]) -> Vec<u32> {
+    let mut ids: Vec<u32> = pull_requests.iter().map(|pr| pr.id).filter(|&id| id > 0).collect();
+    ids.sort();
+    ids.dedup();
+    ids
 }
 
-impl PullRequestInfo {
-    fn from_commit<'a>(c: git2::Commit<'a>) -> Result<PullRequestInfo, String> {
-        let msg = match c.message() {
-            Some(msg) => msg,
-            None => return Err(format!("cannot get commit message for commit {}", c.id()))
-        };
+fn tag_trailers(pr_trailer: bool, released: &[u32]) -> Vec<(&'static str, String)> {
+    if pr_trailer && !released.is_empty() {
+        vec![("Release-PRs", released.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(","))]
+    } else {
+        Vec::new()
+    }
+}
 
-        let (header, body): (Option<String>, String) = {
-            let mut lines_iter = msg.lines();
-            let header = lines_iter.next().map(|s| s.into());
-            let body = lines_iter.join("\n");
-            (header, body.trim().into())
-        };
+// Where the notes go, from the command line or the settings
+struct Destination {
+    template: Option<String>,
+    changelog: Option<String>,
+    output: Option<String>,
+    notify: BTreeMap<String, notify::Target>
+}
+
+// Options which can be set both on the command line and in the settings, the command line
+// taking precedence
+fn apply_settings(config: &mut Config, args: &Args, settings: &settings::Settings) {
+    config.output_format = args.format.or(settings.format).unwrap_or(OutputFormat::Markdown);
+    config.header_patterns = settings.header_patterns.clone();
+    config.group_by = args.group_by.or(settings.group_by).unwrap_or(GroupBy::Nothing);
+    config.repo_name = args.repo_name.clone().or_else(|| settings.repo_name.clone());
+    config.omit_author = !args.no_omit_author && (args.omit_author || settings.omit_author.unwrap_or(false));
+}
+
+fn category_titles(settings: &settings::Settings) -> groups::GroupTitles {
+    if settings.categories.is_empty() { groups::category_titles() } else { settings.categories.clone() }
+}
+
+// Everything the notes are rendered with which can differ between profiles
+struct Output {
+    config: Config,
+    destination: Destination,
+    hooks: hooks::Hooks,
+    group_titles: groups::GroupTitles,
+    categories: groups::GroupTitles,
+    exclude_labels: Vec<String>
+}
 
-        if header.is_none() {
-            return Err(format!("merge commit {} has empty message", c.id()));
+fn output(args: &Args, config: &Config, settings: &settings::Settings) -> Output {
+    let mut config = config.clone();
+    apply_settings(&mut config, args, settings);
+    Output {
+        config,
+        destination: Destination {
+            template: args.template.clone().or_else(|| settings.template.clone()),
+            changelog: args.changelog.clone().or_else(|| settings.changelog.clone()),
+            output: args.output.clone().or_else(|| settings.output.clone()),
+            notify: settings.notify.clone()
+        },
+        hooks: hooks::Hooks::new(settings.hooks.clone()),
+        group_titles: settings.group_titles.clone(),
+        categories: category_titles(settings),
+        exclude_labels: if args.exclude_label.is_empty() { settings.exclude_labels.clone() } else { args.exclude_label.clone() }
+    }
+}
+
+// The walked history, shared by the outputs of all profiles
+struct History<'a> {
+    ranges: &'a [String],
+    walks: &'a [(String, Vec<git2::Oid>)],
+    oids: &'a [git2::Oid],
+    other_commits: &'a OtherCommits,
+    org_map: Option<&'a orgs::OrgMap>,
+    summary: &'a Summary,
+    // Whether the pull requests were processed in the order of the merges, for cherry-picks
+    merge_ordered: bool
+}
+
+fn emit(args: &Args, repo: &Repository, config: &Config, destination: &Destination, hooks: &mut hooks::Hooks, text: String,
+        pull_requests: &[PullRequestInfo]) -> Result<(), String> {
+    hooks.set("notes", json!(text));
+    hooks.run(hooks::Stage::PostGenerate)?;
+    // Nothing gets published in a dry run, so neither do the hooks around publishing run
+    let dry_run = matches!(args.command, Some(Cmd::Release { action: None, ref steps }) if steps.dry_run);
+    if dry_run {
+        return publish_notes(args, repo, config, destination, text, pull_requests);
+    }
+    hooks.run(hooks::Stage::PrePublish)?;
+    publish_notes(args, repo, config, destination, text, pull_requests)?;
+    hooks.run(hooks::Stage::PostPublish)
+}
+
+fn publish_notes(args: &Args, repo: &Repository, config: &Config, destination: &Destination, text: String,
+                 pull_requests: &[PullRequestInfo]) -> Result<(), String>
+{
+    match args.command {
+        Some(Cmd::Release { action: Some(ReleaseAction::Tag { ref version, ref commit_ranges, ref local_user, push, pr_trailer }), .. }) => {
+            release::create_tag(repo, version, release_target(args, commit_ranges), &text, local_user.as_ref().map(|k| &k[..]),
+                                &tag_trailers(pr_trailer, &released_ids(pull_requests)))?;
+            if push {
+                release::push_tag(repo, &args.remote, version)?;
+            }
+            print!("{}", text);
+            return Ok(());
+        }
+        Some(Cmd::Release { action: None, ref steps }) => {
+            let version = steps.version.as_ref().unwrap();
+            return publish(args, repo, destination, steps, version, &text, pull_requests);
         }
-        let header = header.unwrap();
+        _ => {}
+    }
 
-        let header_pattern = regex!(r"Merge pull request #(\d+) from (.+?)/(.+)");
-        let (id, author, branch) = if let Some(captures) = header_pattern.captures(&header) {
-            let id = match captures.at(1).unwrap().parse() {
-                Ok(id) => id,
-                Err(e) => return Err(format!("merge commit {} has invalid pull request id {}: {}", c.id(), captures.at(1).unwrap(), e))
-            };
-            let author = captures.at(2).unwrap().into();
-            let branch = captures.at(3).unwrap().into();
-            (id, author, branch)
-        } else {
-            return Err(format!("merge commit {} has invalid pull request header line: {}", c.id(), header));
-        };
+    match (destination.changelog.as_ref(), destination.output.as_ref()) {
+        (Some(path), _) => {
+            let name = config.release_name.as_ref().map_or("Unreleased", |r| &r[..]);
+            changelog::insert(Path::new(path), name, &release_date(args), &text)
+        }
+        (None, Some(path)) => fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path, e)),
+        (None, None) => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
 
-        Ok(PullRequestInfo {
-            id: id,
-            author: author,
-            branch: branch,
-            name: body
-        })
+// Exiting skips destructors, so the run report is dropped explicitly to get it written
+fn exit_if_stopped(report: report::RunReport) {
+    if let Some(stop) = cancel::stopped() {
+        warn!("Run {}, the output is incomplete", stop.description());
+        drop(report);
+        process::exit(stop.exit_code());
     }
 }
 
 fn main() {
-    let logger_config = fern::DispatchConfig {
-        format: Box::new(|msg, level, _| {
-            format!("{}: {}", level, msg)
-        }),
-        output: vec![fern::OutputConfig::stderr()],
-        level: log::LogLevelFilter::Trace
+    fern::Dispatch::new()
+        .format(|out, msg, record| out.finish(format_args!("{}: {}", record.level(), msg)))
+        .level(log::LevelFilter::Warn)
+        .chain(io::stderr())
+        .apply()
+        .unwrap();
+
+    let args = Args::parse();
+    if let Err(e) = run(&args) {
+        error!("{}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+// Outside of a repository only the global config file is read
+fn config_repository(args: &Args) -> Result<Option<Repository>, Error> {
+    let dir = match args.repo {
+        Some(ref path) => path.clone(),
+        None => try_error!(env::current_dir(), Io, e => "cannot get current directory: {}", e)
+    };
+    Ok(Repository::discover(&dir).ok())
+}
+
+fn run(args: &Args) -> Result<(), Error> {
+    match args.command {
+        Some(Cmd::Cache { ref action }) => {
+            let result = match *action {
+                CacheAction::List => cache::list(),
+                CacheAction::Clear => cache::clear(),
+                CacheAction::Gc { max_age, max_size } => cache::gc(max_age, max_size)
+            };
+            try_error!(result, Io, e => "{}", e);
+            return Ok(());
+        }
+        Some(Cmd::Paths) => {
+            paths::print();
+            return Ok(());
+        }
+        Some(Cmd::SelfUpdate { check, ref host }) => {
+            let current = env!("CARGO_PKG_VERSION");
+            match try_error!(update::self_update(host, check), Io, e => "{}", e) {
+                Some(ref version) if check => println!("git-pull-requests {} is available, this is {}", version, current),
+                Some(version) => println!("Updated git-pull-requests from {} to {}", current, version),
+                None => println!("git-pull-requests {} is up to date", current)
+            }
+            return Ok(());
+        }
+        Some(Cmd::Config { action: ConfigAction::Migrate }) => {
+            let repo = config_repository(args)?;
+            let files = settings::paths(args.config.as_deref(), repo.as_ref().and_then(|r| r.workdir()));
+            let mut migrated = false;
+            for file in files.iter().filter(|f| args.config.is_some() || f.exists()) {
+                let renames = try_error!(settings::migrate(file), Usage, e => "{}", e);
+                for rename in &renames {
+                    println!("{}", rename);
+                }
+                migrated |= !renames.is_empty();
+            }
+            if !migrated {
+                println!("Nothing to migrate");
+            }
+            return Ok(());
+        }
+        Some(Cmd::Config { action: ConfigAction::Validate }) => {
+            let repo = config_repository(args)?;
+            let (_, files) = try_error!(settings::load_files(args.config.as_deref(), repo.as_ref().and_then(|r| r.workdir()), &args.set),
+                                        Usage, e => "{}", e);
+            if files.is_empty() {
+                println!("No configuration files found");
+            }
+            for file in files {
+                println!("{}: ok", file.display());
+            }
+            return Ok(());
+        }
+        Some(Cmd::Auth { ref action }) => {
+            let result = match *action {
+                AuthAction::Login { ref host } => auth::login(host),
+                AuthAction::Status { ref host } => auth::status(host)
+            };
+            try_error!(result, Io, e => "{}", e);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    cancel::install(args.timeout);
+    if let Some(ref dir) = args.record {
+        replay::set_mode(replay::Mode::Record(dir.clone()));
+    } else if let Some(ref dir) = args.replay {
+        replay::set_mode(replay::Mode::Replay(dir.clone()));
+    }
+
+    let mut report = report::RunReport::new(args.run_report.as_ref().map(PathBuf::from));
+    report.enter("open repository");
+
+    let repo = if let Some(ref url) = args.clone {
+        try_error!(open_mirror(url), Repository, e => "{}", e)
+    } else {
+        let dir = match args.repo {
+            Some(ref path) => path.clone(),
+            None => try_error!(env::current_dir(), Io, e => "cannot get current directory: {}", e)
+        };
+        try_error!(Repository::discover(&dir), Repository, e => "cannot open repository {}: {}", dir.display(), e)
     };
-    fern::init_global_logger(logger_config, log::LogLevelFilter::Warn).unwrap();
 
-    let args: Args = Args::docopt()
-        .help(true)
-        .version(VERSION.map(|v| format!("git-pull-requests {}", v)).or_else(|| Some("git-pull-request unknown version".into())))
-        .decode()
-        .unwrap_or_else(|e| e.exit());
+    if args.fetch && args.clone.is_none() {
+        try_error!(fetch_remote(&repo, &args.remote), Repository, e => "cannot fetch remote {}: {}", args.remote, e);
+    }
 
-    let config = Config {
-        output_format: args.flag_format,
-        repo_name: args.flag_repo_name,
-        omit_author: args.flag_omit_author
+    report.enter("load settings");
+    let settings = try_error!(settings::load(args.config.as_deref(), repo.workdir(), &args.set), Usage, e => "{}", e);
+    // With --all-profiles the history is walked and the pull requests are processed once with
+    // the settings outside of profiles, and then rendered for each profile
+    let profiles = if args.all_profiles {
+        if args.command.is_some() {
+            return Err(Error::Usage("--all-profiles cannot be used with subcommands".into()));
+        }
+        if settings.profiles.is_empty() {
+            return Err(Error::Usage("--all-profiles needs [profile.NAME] tables in the config files".into()));
+        }
+        settings.profiles.clone()
+    } else {
+        BTreeMap::new()
+    };
+    let settings = match args.profile {
+        Some(ref name) => try_error!(settings.profile(name), Usage, e => "{}", e),
+        None => settings
     };
 
-    let current_dir = try_error!(env::current_dir(), e => "cannot get current directory: {}", e);
+    let mut config = Config {
+        provider: args.provider,
+        link_base: args.link_base.as_ref().map(|b| b.trim_end_matches('/').to_string()),
+        empty_title_policy: args.empty_title_policy,
+        humanize_branch: args.humanize_branch,
+        branch_prefixes: args.strip_branch_prefix.clone()
+            .map(|s| s.split(',').map(|p| p.trim().into()).filter(|p: &String| !p.is_empty()).collect())
+            .unwrap_or(vec![]),
+        title_from: args.title_from,
+        octopus: args.octopus,
+        include_squash: args.include_squash,
+        include_plain_merges: args.include_plain_merges,
+        include_direct_commits: args.include_direct_commits,
+        include_other_changes: args.include_other_changes,
+        first_parent: args.first_parent,
+        patchsets: args.patchsets,
+        since: args.since,
+        until: args.until,
+        show_commits: args.show_commits,
+        show_first_release: args.show_first_release,
+        show_reviewers: args.show_reviewers,
+        date_format: if args.show_date { Some(args.date_format.clone()) } else { None },
+        show_sha: args.show_sha,
+        show_email: args.show_email,
+        quiet: args.quiet,
+        normalize: args.normalize,
+        heading_level: usize::from(args.heading_level),
+        section_style: args.section_style,
+        release_name: None,
+        announce_url: args.announce_url.clone(),
+        max_post_length: args.max_post_length,
+        translate_cmd: args.translate_cmd.clone(),
+        jobs: args.jobs,
+        ..Config::default()
+    };
+    apply_settings(&mut config, args, &settings);
 
-    let repo = try_error!(Repository::discover(current_dir), e => "cannot open repository: {}", e);
+    // An explicit --link-base is used as is, otherwise pull requests link to the forge of the remote
+    if config.link_base.is_none() {
+        if let Some((host, repository)) = remote_repository(&repo, args) {
+            config.forges = forge::for_links(config.provider, &host, &repository);
+            config.link_base = Some(format!("https://{}/{}", host, repository));
+        }
+    }
 
-    let mut revwalk = try_error!(repo.revwalk(), e => "cannot get revwalk: {}", e);
+    let commit_ranges = match args.command {
+        Some(Cmd::Find { pr_id, ref commit_range }) => {
+            let range = commit_range.as_ref().map_or("HEAD", |r| &r[..]);
+            print!("{}", try_error!(find_pr(&repo, pr_id, range, &config), Revwalk, e => "{}", e));
+            return Ok(());
+        }
+        Some(Cmd::Divergence { ref branch_a, ref branch_b }) => {
+            for (title, prs) in try_error!(divergence(&repo, branch_a, branch_b, &config), Revwalk, e => "{}", e) {
+                println!("{}", config.output_format.format_section_header(&title, &config));
+                for pr in prs {
+                    println!("{}", config.output_format.format(&pr, &config));
+                }
+            }
+            return Ok(());
+        }
+        Some(Cmd::WhichPr { ref target }) => {
+            match try_error!(which_pr(&repo, target, &config), Revwalk, e => "{}", e) {
+                Introduction::PullRequest(pr, merge) => {
+                    println!("{}", config.output_format.format(&pr, &config));
+                    println!("Merged in {}", merge);
+                }
+                Introduction::Direct(commit) => println!("Commit {} was made directly on the branch", commit)
+            }
+            return Ok(());
+        }
+        Some(Cmd::Stats { ref commit_ranges }) | Some(Cmd::Backport { ref commit_ranges, .. }) => commit_ranges.clone(),
+        Some(Cmd::Preview { ref commit_range }) => vec![commit_range.clone()],
+        Some(Cmd::Release { .. }) => {
+            let (version, commit_ranges) = release_version(args).unwrap();
+            let creates_tag = match args.command {
+                Some(Cmd::Release { action: None, ref steps }) => !steps.no_tag,
+                _ => true
+            };
+            if creates_tag && release::tag_exists(&repo, version) {
+                return Err(Error::Usage(format!("tag {} already exists", version)));
+            }
+            config.release_name = Some(version.clone());
+            if commit_ranges.is_empty() {
+                vec![try_error!(release::unreleased_range(&repo, &args.to), Revwalk, e => "{}", e)]
+            } else {
+                commit_ranges.clone()
+            }
+        }
+        _ => args.commit_ranges.clone()
+    };
 
-    try_error!(revwalk.push_range(&args.arg_commit_range), e => "error pushing range {}: {}", args.arg_commit_range, e);
-    revwalk.set_sorting(git2::SORT_TIME);
+    report.enter("resolve range");
+    let ranges = if let Some(ref tag) = args.release {
+        vec![try_error!(release_range(&repo, tag), Revwalk, e => "{}", e)]
+    } else if let Some(n) = args.last_n_releases {
+        vec![try_error!(last_releases_range(&repo, n), Revwalk, e => "{}", e)]
+    } else if args.all_releases {
+        vec![try_error!(latest_tag(&repo, "HEAD"), Revwalk, e => "{}", e)]
+    } else if args.since_last_tag {
+        vec![try_error!(since_last_tag_range(&repo, &args.to), Revwalk, e => "{}", e)]
+    } else if let Some(ref tag) = args.from_tag {
+        vec![format!("{}..{}", tag, args.to)]
+    } else {
+        commit_ranges
+    };
+    if ranges.is_empty() {
+        return Err(Error::Usage("no commit range given".into()));
+    }
+    info!("Using range {}", ranges.join(" "));
+    config.release_name = args.release_name.clone()
+        .or_else(|| config.release_name.take())
+        .or_else(|| args.release.clone())
+        .or_else(|| ranges.last().and_then(|r| r.rsplit("..").next())
+                 .filter(|end| release::tag_exists(&repo, end)).map(|s| s.to_string()));
 
-    let pull_requests = revwalk
-        .map(|oid| repo.find_commit(oid).unwrap())
-        .filter(|c| c.parents().len() == 2)  // only merge commits
-        .map(PullRequestInfo::from_commit);
+    for range in &ranges {
+        try_error!(validate_range(&repo, range), Revwalk, e => "{}", e);
+    }
 
-    let mut any_errors = false;
-    let pull_requests: Vec<PullRequestInfo> = pull_requests.filter_map(|pr| match pr {
-        Ok(pr) => Some(pr),
-        Err(e) => {
-            any_errors = true;
-            warn!("Error parsing commit: {}", e);
-            None
+    let mut outputs = Vec::new();
+    if profiles.is_empty() {
+        outputs.push(output(args, &config, &settings));
+    }
+    for (name, profile) in &profiles {
+        let output = output(args, &config, profile);
+        if output.destination.changelog.is_none() && output.destination.output.is_none() {
+            return Err(Error::Usage(format!("profile {} sets neither output nor changelog", name)));
+        }
+        let patterns = |s: &settings::Settings| s.header_patterns.iter().map(|p| p.as_str().to_string()).collect::<Vec<_>>();
+        if patterns(profile) != patterns(&settings) && !config.quiet {
+            warn!("Header patterns of profile {} are ignored with --all-profiles", name);
+        }
+        outputs.push(output);
+    }
+    for output in &outputs {
+        if (output.config.group_by == GroupBy::Label || output.config.group_by == GroupBy::Origin) && !args.enrich {
+            return Err(Error::Usage("--group-by label and --group-by origin require --enrich".into()));
+        }
+        if !output.exclude_labels.is_empty() && !args.enrich {
+            return Err(Error::Usage("excluding pull requests by label requires --enrich".into()));
         }
-    }).collect();
+    }
+
+    report.enter("walk history");
+    let collector = Collector::new(&repo, &config);
+    let mut walks = Vec::new();
+    for range in &ranges {
+        walks.push((range.clone(), try_error!(collector.walk(range, &args.exclude_reachable_from), Revwalk, e => "{}", e)));
+    }
+    let oids = collector.merge_walks(&walks);
+
+    if let Some(Cmd::Preview { .. }) = args.command {
+        print!("{}", try_error!(preview(&repo, &oids, &config), Revwalk, e => "cannot preview range: {}", e));
+        return Ok(());
+    }
+
+    report.enter("parse commits");
+    let Collection { oids, pull_requests, plain_merges, direct_commits, other_changes, summary, invalid, warnings } =
+        try_error!(collector.collect(&ranges, oids), Revwalk, e => "{}", e);
+    report.count("commits", summary.commits);
+    report.count("merges", summary.merges);
+    report.count("pull_requests", summary.pull_requests);
 
-    if any_errors {
-        if args.flag_skip_invalid {
+    report.enter("process pull requests");
+    let remote = if args.enrich || matches!(config.empty_title_policy, EmptyTitlePolicy::Api) {
+        match remote_repository(&repo, args) {
+            Some(remote) => Some(remote),
+            None => return Err(Error::Usage(format!("cannot determine the repository from remote {}", args.remote)))
+        }
+    } else {
+        None
+    };
+    let enrich = if args.enrich {
+        Some(Enrichment {
+            concurrency: args.api_concurrency.unwrap_or(args.jobs),
+            resume: args.resume,
+            reviewers: args.show_reviewers
+        })
+    } else {
+        None
+    };
+    let processing = Processing {
+        handle_reverts: args.handle_reverts,
+        remote,
+        enrich,
+        backport_candidates: args.backport_candidates.clone().map(|branch| (branch, args.backport_marker.clone())),
+        author_check_cmd: args.author_check_cmd.clone(),
+        categories: category_titles(&settings),
+        sort: args.sort,
+        reverse: args.reverse
+    };
+    let other_commits = OtherCommits { plain_merges, direct_commits, other_changes };
+    let Processed { pull_requests, other_commits, duplicates, failed_authors } =
+        process(&repo, &oids, pull_requests, other_commits, &processing, &config)?;
+    if !duplicates.is_empty() && !config.quiet {
+        warn!("Pull requests merged more than once, listing only their latest merge: {}", duplicates.join(", "));
+    }
+    if !failed_authors.is_empty() {
+        warn!("Authors failing the author check: {}", failed_authors.join(", "));
+    }
+    eprint!("{}", summary);
+
+    if args.strict && warnings > 0 {
+        return Err(Error::Parse(format!("{} commits produced warnings, aborting because of --strict", warnings)));
+    }
+    if invalid > 0 {
+        let invalid_pct = invalid as f64 * 100.0 / summary.merges as f64;
+        if args.skip_invalid {
             warn!("Some commits couldn't be parsed, skipping them");
+        } else if args.max_invalid_ratio.is_some_and(|max| invalid_pct <= max) {
+            warn!("{:.1}% of merge commits couldn't be parsed, skipping them", invalid_pct);
         } else {
-            error!("Some commits couldn't be parsed, aborting");
-            return;
+            return Err(Error::Parse("some commits couldn't be parsed, aborting".into()));
+        }
+    }
+    if pull_requests.is_empty() {
+        if args.fail_if_empty {
+            return Err(Error::Empty("no pull requests found, aborting because of --fail-if-empty".into()));
+        } else if !args.allow_empty && !config.quiet {
+            warn!("No pull requests found");
         }
     }
 
-    for pr in pull_requests {
-        println!("{}", config.output_format.format(&pr, &config));
+    report.enter("render");
+    let org_map = match args.org_map {
+        Some(ref path) => Some(try_error!(orgs::load(Path::new(path)), Io, e => "{}", e)),
+        None => None
+    };
+
+    if let Some(Cmd::Stats { .. }) = args.command {
+        let paths = if args.paths {
+            Some(try_error!(stats::changed_paths(repo.path(), &pull_requests, args.jobs), Repository, e => "cannot get changed paths: {}", e))
+        } else {
+            None
+        };
+        match config.output_format {
+            OutputFormat::Json => {
+                let json = stats::to_json(&pull_requests, org_map.as_ref(), paths.as_ref());
+                println!("{}", serde_json::to_string_pretty(&json).unwrap());
+            }
+            _ => print!("{}", stats::report(&pull_requests, org_map.as_ref(), paths.as_ref()))
+        }
+        exit_if_stopped(report);
+        return Ok(());
+    }
+
+    if let Some(Cmd::Backport { ref branch, ref pick, dry_run, .. }) = args.command {
+        let mut selected = try_error!(select_backports(pull_requests, pick), Usage, e => "{}", e);
+        let order = try_error!(merge_order(&repo, &oids), Revwalk, e => "cannot order merges: {}", e);
+        selected.sort_by_key(|pr| git2::Oid::from_str(&pr.sha).ok().and_then(|oid| order.get(&oid).cloned()));
+        let picked = try_error!(backport::cherry_pick(&repo, branch, &selected.iter().collect::<Vec<_>>(), dry_run),
+                                Repository, e => "{}", e);
+        if picked.is_empty() {
+            println!("Nothing to cherry-pick");
+        }
+        for pr in picked {
+            println!("{} {} {}", if dry_run { "Would cherry-pick" } else { "Cherry-picked" }, pr.reference(),
+                     pr.name.lines().next().unwrap_or(""));
+        }
+        exit_if_stopped(report);
+        return Ok(());
+    }
+
+    let history = History {
+        ranges: &ranges,
+        walks: &walks,
+        oids: &oids,
+        other_commits: &other_commits,
+        org_map: org_map.as_ref(),
+        summary: &summary,
+        merge_ordered: config.output_format.oldest_first()
+    };
+    for output in &mut outputs {
+        render(args, &repo, &history, output, pull_requests.clone())?;
+    }
+    exit_if_stopped(report);
+    Ok(())
+}
+
+// Pull requests given with --pick, or chosen from a numbered list on the terminal
+fn select_backports(pull_requests: Vec<PullRequestInfo>, pick: &[u32]) -> Result<Vec<PullRequestInfo>, String> {
+    if !pick.is_empty() {
+        if let Some(id) = pick.iter().find(|&&id| !pull_requests.iter().any(|pr| pr.id == id)) {
+            return Err(format!("pull request {} is not in the range", id));
+        }
+        return Ok(pull_requests.into_iter().filter(|pr| pick.contains(&pr.id)).collect());
+    }
+    if pull_requests.is_empty() {
+        return Ok(pull_requests);
+    }
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Err("choose the pull requests with --pick when not on a terminal".into());
+    }
+    for (i, pr) in pull_requests.iter().enumerate() {
+        eprintln!("{:>4}  {} {}", i + 1, pr.reference(), pr.name.lines().next().unwrap_or(""));
+    }
+    eprint!("Pull requests to cherry-pick, e.g. 1 3-5 or all: ");
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line).map_err(|e| format!("cannot read the selection: {}", e))?;
+    let chosen = backport::parse_selection(&line, pull_requests.len())?;
+    Ok(pull_requests.into_iter().enumerate().filter(|&(i, _)| chosen.contains(&(i + 1))).map(|(_, pr)| pr).collect())
+}
+
+fn render(args: &Args, repo: &Repository, history: &History, output: &mut Output, mut pull_requests: Vec<PullRequestInfo>)
+    -> Result<(), Error>
+{
+    let Output { ref config, ref destination, ref mut hooks, ref group_titles, ref categories, ref exclude_labels } = *output;
+    categorize(&mut pull_requests, categories);
+    let mut pull_requests = without_labels(pull_requests, exclude_labels);
+    if config.lists_commits() {
+        try_error!(list_commits(repo, &mut pull_requests), Repository, e => "cannot list commits: {}", e);
+    }
+    if config.output_format.oldest_first() && !history.merge_ordered {
+        let order = try_error!(merge_order(repo, history.oids), Revwalk, e => "cannot order merges: {}", e);
+        pull_requests.sort_by_key(|pr| git2::Oid::from_str(&pr.sha).ok().and_then(|oid| order.get(&oid).cloned()));
+    }
+
+    hooks.set("repo", json!(config.repo_name));
+    hooks.set("ranges", json!(history.ranges));
+    hooks.set("release", json!(config.release_name));
+    hooks.set("pull_requests", pull_requests_json(&pull_requests));
+    hooks.set("summary", history.summary.to_json());
+    try_error!(hooks.run(hooks::Stage::PreGenerate), Io, e => "{}", e);
+
+    if let Some(ref path) = destination.template {
+        let document = template_document(args, repo, config, history.ranges, &pull_requests);
+        let document = try_error!(template::render(Path::new(path), &pull_requests, &document, config.normalize),
+                                  Io, e => "{}", e);
+        let document = try_error!(translate_section(config, document), Io, e => "{}", e);
+        try_error!(emit(args, repo, config, destination, hooks, document, &pull_requests), Io, e => "{}", e);
+        return Ok(());
+    }
+
+    if let Some(document) = config.output_format.format_document(&pull_requests, history.summary, config) {
+        let document = try_error!(translate_section(config, document + "\n"), Io, e => "{}", e);
+        try_error!(emit(args, repo, config, destination, hooks, document, &pull_requests), Io, e => "{}", e);
+        return Ok(());
+    }
+
+    let mut output = String::new();
+    if let Some(header) = config.output_format.format_header() {
+        writeln!(&mut output, "{}", header).unwrap();
+    }
+    let sponsors = if args.show_sponsors {
+        match history.org_map {
+            Some(org_map) => orgs::contributions(&pull_requests, org_map),
+            None => return Err(Error::Usage("--show-sponsors requires --org-map".into()))
+        }
+    } else {
+        BTreeMap::new()
+    };
+
+    let feature_flags = match args.feature_flags {
+        Some(ref path) => {
+            let rules = try_error!(flags::load(Path::new(path)), Io, e => "{}", e);
+            try_error!(flags::extract(repo, &pull_requests, &rules, args.jobs), Repository, e => "cannot extract feature flags: {}", e)
+        }
+        None => BTreeMap::new()
+    };
+
+    let titles = match args.group_titles {
+        _ if config.group_by == GroupBy::Nothing => Vec::new(),
+        Some(ref path) => try_error!(groups::load(Path::new(path)), Io, e => "{}", e),
+        None if config.group_by == GroupBy::Category => categories.clone(),
+        None if !group_titles.is_empty() => group_titles.clone(),
+        None if config.group_by == GroupBy::Origin => groups::origin_titles(),
+        None => groups::default_titles()
+    };
+    let layout = Layout {
+        split_by_range: args.split_by_range,
+        split_by_tag: args.split_by_tag || args.all_releases,
+        group_titles: titles,
+        compliance: args.compliance,
+        breaking_changes: args.breaking_changes,
+        deprecations: args.deprecations,
+        max_length: args.max_length,
+        allow_empty: args.allow_empty
+    };
+    // Publishing a release needs the pull requests, which are taken apart into sections here
+    let released = pull_requests.clone();
+    let sections = arrange(repo, history.walks, history.oids, pull_requests, &layout, config)?;
+    let rendered = render_notes(&sections, history.other_commits, &feature_flags, &sponsors, &layout, config);
+
+    for section in rendered {
+        output.push_str(&try_error!(translate_section(config, section), Io, e => "{}", e));
     }
+    try_error!(emit(args, repo, config, destination, hooks, output, &released), Io, e => "{}", e);
+    Ok(())
 }
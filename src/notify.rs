@@ -0,0 +1,96 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use forge;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Kind {
+    Matrix,
+    Mattermost,
+    Teams
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Matrix => "matrix",
+            Kind::Mattermost => "mattermost",
+            Kind::Teams => "teams"
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Kind, String> {
+        match s {
+            "matrix" => Ok(Kind::Matrix),
+            "mattermost" => Ok(Kind::Mattermost),
+            "teams" => Ok(Kind::Teams),
+            _ => Err(format!("unknown notification type: {}", s))
+        }
+    }
+}
+
+// A [notify.NAME] table of the settings; the URL is that of the homeserver for Matrix and
+// of the incoming webhook for Mattermost and Teams, which need neither a room nor a token
+#[derive(Clone, PartialEq, Debug)]
+pub struct Target {
+    pub kind: Kind,
+    pub url: String,
+    pub room: Option<String>,
+    pub token: Option<String>
+}
+
+fn message(version: &str, url: &str, notes: &str) -> String {
+    let title = if url.is_empty() { format!("Released {}", version) } else { format!("Released {}: {}", version, url) };
+    format!("{}\n\n{}", title, notes.trim_end())
+}
+
+// Room ids like !abc:example.org go into the path of the request
+fn escape(segment: &str) -> String {
+    segment.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'!' | b':' => (b as char).to_string(),
+        _ => format!("%{:02X}", b)
+    }).collect()
+}
+
+impl Target {
+    pub fn send(&self, version: &str, url: &str, notes: &str) -> Result<(), String> {
+        let text = message(version, url, notes);
+        match self.kind {
+            Kind::Matrix => {
+                let room = self.room.as_ref().map_or("", |r| &r[..]);
+                let token = self.token.as_ref().map_or("", |t| &t[..]);
+                // Matrix deduplicates messages by the transaction id, so it has to differ between releases
+                let transaction = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+                let endpoint = format!("{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}-{}",
+                                       self.url.trim_end_matches('/'), escape(room), escape(version), transaction);
+                forge::send("PUT", &endpoint, &[format!("Authorization: Bearer {}", token)],
+                            Some(&json!({"msgtype": "m.text", "body": text}))).map(|_| ())
+            }
+            Kind::Mattermost => forge::post(&self.url, &[], &json!({"text": text})),
+            Kind::Teams => forge::post(&self.url, &[], &json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": format!("Released {}", version),
+                "text": text
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_messages() {
+        assert_eq!(message("v1.2.0", "https://github.com/o/r/releases/tag/v1.2.0", "## Fixes\n\n* Crash\n\n"),
+                   "Released v1.2.0: https://github.com/o/r/releases/tag/v1.2.0\n\n## Fixes\n\n* Crash");
+        assert_eq!(message("v1.2.0", "", "* Crash\n"), "Released v1.2.0\n\n* Crash");
+        assert_eq!(escape("!abc:example.org"), "!abc:example.org");
+        assert_eq!(escape("#releases/x"), "%23releases%2Fx");
+    }
+}
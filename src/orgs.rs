@@ -0,0 +1,47 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use PullRequestInfo;
+
+pub type OrgMap = HashMap<String, String>;
+
+// Mapping files contain `author = organization` lines; empty lines and lines starting with # are ignored
+pub fn load(path: &Path) -> Result<OrgMap, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+    let mut result = HashMap::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(2, '=').map(|p| p.trim()).collect();
+        if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
+            return Err(format!("{}:{}: expected `author = organization`", path.display(), i + 1));
+        }
+        result.insert(parts[0].to_string(), parts[1].to_string());
+    }
+    Ok(result)
+}
+
+pub struct OrgContribution {
+    pub authors: BTreeSet<String>,
+    pub pull_requests: usize
+}
+
+pub fn contributions(pull_requests: &[PullRequestInfo], orgs: &OrgMap) -> BTreeMap<String, OrgContribution> {
+    let mut result = BTreeMap::new();
+    for pr in pull_requests {
+        if let Some(org) = orgs.get(&pr.author) {
+            let entry = result.entry(org.clone()).or_insert(OrgContribution {
+                authors: BTreeSet::new(),
+                pull_requests: 0
+            });
+            entry.authors.insert(pr.author.clone());
+            entry.pull_requests += 1;
+        }
+    }
+    result
+}
@@ -0,0 +1,72 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_NAME: &str = "git-pull-requests";
+
+fn home_dir() -> Option<PathBuf> {
+    env::home_dir()
+}
+
+#[cfg(windows)]
+fn platform_config_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(windows)]
+fn platform_cache_dir() -> Option<PathBuf> {
+    env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+#[cfg(windows)]
+fn platform_state_dir() -> Option<PathBuf> {
+    env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_config_dir() -> Option<PathBuf> {
+    home_dir().map(|h| h.join("Library").join("Application Support"))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_cache_dir() -> Option<PathBuf> {
+    home_dir().map(|h| h.join("Library").join("Caches"))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_state_dir() -> Option<PathBuf> {
+    home_dir().map(|h| h.join("Library").join("Application Support"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(|| home_dir().map(|h| h.join(".config")))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME").map(PathBuf::from).or_else(|| home_dir().map(|h| h.join(".cache")))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_state_dir() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME").map(PathBuf::from).or_else(|| home_dir().map(|h| h.join(".local").join("state")))
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    platform_config_dir().map(|d| d.join(APP_NAME))
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    platform_cache_dir().map(|d| d.join(APP_NAME))
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    platform_state_dir().map(|d| d.join(APP_NAME))
+}
+
+pub fn print() {
+    let show = |dir: Option<PathBuf>| dir.map_or("<unknown>".to_string(), |d| d.display().to_string());
+    println!("config: {}", show(config_dir()));
+    println!("cache:  {}", show(cache_dir()));
+    println!("state:  {}", show(state_dir()));
+}
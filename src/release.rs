@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use git2::Repository;
+
+use latest_tag;
+
+// A release contains everything since the most recent tag, or the whole history
+// if nothing has been tagged yet
+pub fn unreleased_range(repo: &Repository, to: &str) -> Result<String, String> {
+    repo.revparse_single(&format!("{}^{{commit}}", to)).map_err(|e| format!("cannot resolve {}: {}", to, e))?;
+    Ok(match latest_tag(repo, to) {
+        Ok(tag) => format!("{}..{}", tag, to),
+        Err(_) => to.to_string()
+    })
+}
+
+pub fn tag_exists(repo: &Repository, name: &str) -> bool {
+    repo.find_reference(&format!("refs/tags/{}", name)).is_ok()
+}
+
+fn git(repo: &Repository) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir").arg(repo.path());
+    cmd
+}
+
+// libgit2 cannot sign tags, so git itself is used, with the terminal left to it in case
+// gpg asks for a passphrase; verbatim cleanup keeps markdown headers in the notes, which
+// git would otherwise strip as comments; trailers go into a paragraph of their own at the end,
+// where git interpret-trailers finds them
+pub fn create_tag(repo: &Repository, name: &str, target: &str, notes: &str, key: Option<&str>, trailers: &[(&str, String)])
+    -> Result<(), String>
+{
+    let mut cmd = git(repo);
+    cmd.arg("tag").arg("--cleanup=verbatim").arg("--file=-");
+    match key {
+        Some(key) => cmd.arg("--local-user").arg(key),
+        None => cmd.arg("--sign")
+    };
+    let mut child = cmd.arg(name).arg(target)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run git: {}", e))?;
+
+    let mut message = format!("{}\n\n{}", name, notes);
+    if !trailers.is_empty() {
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
+        message.push('\n');
+        for (key, value) in trailers {
+            message.push_str(&format!("{}: {}\n", key, value));
+        }
+    }
+    child.stdin.take().unwrap().write_all(message.as_bytes())
+        .map_err(|e| format!("cannot write to git: {}", e))?;
+
+    let status = child.wait().map_err(|e| format!("git tag failed: {}", e))?;
+    if !status.success() {
+        return Err(format!("cannot create tag {}: git tag exited with {}", name, status));
+    }
+    Ok(())
+}
+
+pub fn push_tag(repo: &Repository, remote: &str, name: &str) -> Result<(), String> {
+    let status = git(repo).arg("push").arg(remote).arg(format!("refs/tags/{}", name))
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| format!("cannot run git: {}", e))?;
+    if !status.success() {
+        return Err(format!("cannot push tag {} to {}: git push exited with {}", name, remote, status));
+    }
+    Ok(())
+}
+
+// The command gets the version and the URL of the published release, if any, as $1 and $2
+// and the notes on stdin
+pub fn notify(cmd: &str, version: &str, url: &str, notes: &str) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c").arg(cmd).arg("sh").arg(version).arg(url)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run notification command: {}", e))?;
+    // Commands which don't need the notes may exit without reading them
+    match child.stdin.take().unwrap().write_all(notes.as_bytes()) {
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => return Err(format!("cannot write to notification command: {}", e)),
+        Ok(_) => {}
+    }
+
+    let status = child.wait().map_err(|e| format!("notification command failed: {}", e))?;
+    if !status.success() {
+        return Err(format!("notification command exited with {}", status));
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde_json::{self, Value};
+
+// API responses are recorded into or replayed from a directory for the whole run, so that
+// enriched runs can be repeated in tests and without network access
+pub enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf)
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+pub fn set_mode(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+pub fn replaying() -> bool {
+    matches!(MODE.get(), Some(Mode::Replay(_)))
+}
+
+// One file per request, named after the method and the URL; tokens are never part of it
+fn file_name(method: &str, url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name: String = format!("{} {}", method, url).chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+    format!("{}.json", name)
+}
+
+// Returns None unless replaying; a request which wasn't recorded is an error rather than
+// going to the network
+pub fn lookup(method: &str, url: &str) -> Option<Result<Value, String>> {
+    let dir = match MODE.get() {
+        Some(Mode::Replay(dir)) => dir,
+        _ => return None
+    };
+    let path = dir.join(file_name(method, url));
+    let mut text = String::new();
+    Some(match File::open(&path) {
+        Ok(mut f) => f.read_to_string(&mut text)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))
+            .and_then(|_| serde_json::from_str::<Value>(&text).map_err(|e| format!("invalid recording {}: {}", path.display(), e)))
+            .and_then(|json| json.get("response").cloned()
+                .ok_or_else(|| format!("invalid recording {}: no response", path.display()))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound =>
+            Err(format!("no recorded response for {} {} in {}", method, url, dir.display())),
+        Err(e) => Err(format!("cannot open {}: {}", path.display(), e))
+    })
+}
+
+pub fn record(method: &str, url: &str, response: &Value) -> Result<(), String> {
+    let dir = match MODE.get() {
+        Some(Mode::Record(dir)) => dir,
+        _ => return Ok(())
+    };
+    fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+
+    let recording = json!({"method": method, "url": url, "response": response});
+    let path = dir.join(file_name(method, url));
+    File::create(&path).and_then(|mut f| f.write_all(serde_json::to_string_pretty(&recording).unwrap().as_bytes()))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde_json::{self, Value};
+use time;
+
+struct Phase {
+    name: String,
+    duration_ms: f64
+}
+
+// Records executed phases with their timings; the report is written when it is dropped,
+// so early exits on errors are recorded as well
+pub struct RunReport {
+    path: Option<PathBuf>,
+    started_at: u64,
+    phases: Vec<Phase>,
+    current: Option<(String, u64)>,
+    counters: BTreeMap<String, usize>
+}
+
+impl RunReport {
+    pub fn new(path: Option<PathBuf>) -> RunReport {
+        RunReport {
+            path,
+            started_at: time::precise_time_ns(),
+            phases: Vec::new(),
+            current: None,
+            counters: BTreeMap::new()
+        }
+    }
+
+    pub fn enter(&mut self, name: &str) {
+        self.finish_phase();
+        self.current = Some((name.to_string(), time::precise_time_ns()));
+    }
+
+    pub fn count(&mut self, name: &str, value: usize) {
+        self.counters.insert(name.to_string(), value);
+    }
+
+    fn finish_phase(&mut self) {
+        if let Some((name, started)) = self.current.take() {
+            self.phases.push(Phase {
+                name,
+                duration_ms: (time::precise_time_ns() - started) as f64 / 1e6
+            });
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "version": ::VERSION.unwrap_or("unknown"),
+            "args": env::args().skip(1).collect::<Vec<_>>(),
+            "total_ms": (time::precise_time_ns() - self.started_at) as f64 / 1e6,
+            "phases": self.phases.iter().map(|p| json!({"name": p.name, "duration_ms": p.duration_ms})).collect::<Vec<_>>(),
+            "counters": self.counters
+        })
+    }
+}
+
+impl Drop for RunReport {
+    fn drop(&mut self) {
+        let path = match self.path.take() {
+            Some(path) => path,
+            None => return
+        };
+        self.finish_phase();
+
+        let data = serde_json::to_string_pretty(&self.to_json()).unwrap();
+        match File::create(&path).and_then(|mut f| f.write_all(data.as_bytes())) {
+            Ok(_) => info!("Run report written to {}", path.display()),
+            Err(e) => warn!("cannot write run report to {}: {}", path.display(), e)
+        }
+    }
+}
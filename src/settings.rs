@@ -0,0 +1,679 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use toml;
+
+use {GroupBy, OutputFormat};
+use hooks;
+use notify;
+use paths::config_dir;
+
+pub const FILE_NAME: &str = ".git-pull-requests.toml";
+
+// Values from configuration files; command line options take precedence over them
+#[derive(Default, Clone)]
+pub struct Settings {
+    pub repo_name: Option<String>,
+    pub format: Option<OutputFormat>,
+    pub omit_author: Option<bool>,
+    pub header_patterns: Vec<Regex>,
+    pub group_by: Option<GroupBy>,
+    pub group_titles: Vec<(String, String)>,
+    pub categories: Vec<(String, String)>,
+    pub hooks: BTreeMap<String, String>,
+    pub template: Option<String>,
+    pub changelog: Option<String>,
+    pub output: Option<String>,
+    pub exclude_labels: Vec<String>,
+    // Targets of release notifications by name, from the [notify.NAME] tables
+    pub notify: BTreeMap<String, notify::Target>,
+    // Settings of each [profile.NAME] table, which override the values outside of profiles
+    pub profiles: BTreeMap<String, Settings>
+}
+
+impl Settings {
+    pub fn profile(mut self, name: &str) -> Result<Settings, String> {
+        self.profiles.remove(name).ok_or_else(|| format!("unknown profile {}", name))
+    }
+}
+
+pub fn global_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("config.toml"))
+}
+
+fn read(path: &Path) -> Result<Option<(String, toml::Table)>, String> {
+    let mut text = String::new();
+    match File::open(path) {
+        Ok(mut f) => {
+            f.read_to_string(&mut text).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot open {}: {}", path.display(), e))
+    }
+
+    match text.parse::<toml::Table>() {
+        Ok(table) => Ok(Some((text, table))),
+        Err(e) => Err(format!("{}: {}", path.display(), e))
+    }
+}
+
+// A configuration file being merged, used to point errors at the line of the offending key;
+// the section is the profile table being merged, if any
+struct Source<'a> {
+    path: &'a Path,
+    text: &'a str,
+    section: &'a str
+}
+
+fn key_at(line: &str, key: &str) -> bool {
+    line.trim_start().strip_prefix(key).is_some_and(|rest| {
+        let rest = rest.trim_start();
+        rest.starts_with('=') || rest.starts_with('.')
+    })
+}
+
+impl<'a> Source<'a> {
+    fn qualified(&self, key: &str) -> String {
+        if self.section.is_empty() { key.to_string() } else { format!("{}.{}", self.section, key) }
+    }
+
+    // Finds `[a.b]` or a table within it like `[a.b.c]` for the key `a.b`, or the line in the
+    // enclosing section which sets it, like `b = ...` in `[a]` or `a.b = ...` at the top level
+    fn line_of(&self, key: &str) -> Option<usize> {
+        let key = self.qualified(key);
+        let mut section = String::new();
+        for (i, line) in self.text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                section = trimmed.trim_matches(|c| c == '[' || c == ']').trim().to_string();
+                if section == key || section.strip_prefix(&key[..]).is_some_and(|r| r.starts_with('.')) {
+                    return Some(i + 1);
+                }
+                continue;
+            }
+            let rest = if section.is_empty() {
+                Some(&key[..])
+            } else {
+                key.strip_prefix(&section[..]).and_then(|r| r.strip_prefix('.'))
+            };
+            if rest.is_some_and(|rest| key_at(line, rest.split('.').next().unwrap())) {
+                return Some(i + 1);
+            }
+        }
+        None
+    }
+
+    fn error(&self, key: &str, msg: String) -> String {
+        match self.line_of(key) {
+            Some(line) => format!("{}:{}: {}", self.path.display(), line, msg),
+            None => format!("{}: {}", self.path.display(), msg)
+        }
+    }
+
+    fn mismatch(&self, key: &str, expected: &str, value: &toml::Value) -> String {
+        self.error(key, format!("{} must be {}, found {}", self.qualified(key), expected, value.type_str()))
+    }
+
+    // Replaces ${NAME} with the value of the environment variable, so that secrets and
+    // machine-specific values can stay out of the file
+    fn interpolate(&self, key: &str, value: &str) -> Result<String, String> {
+        let mut result = String::new();
+        let mut last = 0;
+        for caps in regex!(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").captures_iter(value) {
+            let (whole, name) = (caps.get(0).unwrap(), &caps[1]);
+            let var = env::var(name).map_err(|_| {
+                self.error(key, format!("{} refers to environment variable {}, which is not set", self.qualified(key), name))
+            })?;
+            result.push_str(&value[last..whole.start()]);
+            result.push_str(&var);
+            last = whole.end();
+        }
+        result.push_str(&value[last..]);
+        Ok(result)
+    }
+
+    fn string(&self, table: &toml::Table, key: &str) -> Result<Option<String>, String> {
+        match table.get(key) {
+            Some(toml::Value::String(s)) => self.interpolate(key, s).map(Some),
+            Some(v) => Err(self.mismatch(key, "a string", v)),
+            None => Ok(None)
+        }
+    }
+
+    fn strings(&self, table: &toml::Table, key: &str) -> Result<Option<Vec<String>>, String> {
+        match table.get(key) {
+            Some(toml::Value::Array(values)) => values.iter().map(|v| match *v {
+                toml::Value::String(ref s) => self.interpolate(key, s),
+                ref v => Err(self.mismatch(key, "an array of strings", v))
+            }).collect::<Result<_, _>>().map(Some),
+            Some(v) => Err(self.mismatch(key, "an array", v)),
+            None => Ok(None)
+        }
+    }
+
+    fn titles(&self, table: &toml::Table, key: &str) -> Result<Vec<(String, String)>, String> {
+        match table.get(key) {
+            Some(toml::Value::Table(titles)) => titles.iter().map(|(k, v)| match *v {
+                toml::Value::String(ref title) => {
+                    Ok((k.to_lowercase(), self.interpolate(&format!("{}.{}", key, k), title)?))
+                }
+                ref v => Err(self.mismatch(&format!("{}.{}", key, k), "a string", v))
+            }).collect(),
+            Some(v) => Err(self.mismatch(key, "a table", v)),
+            None => Ok(Vec::new())
+        }
+    }
+
+    fn target(&self, key: &str, table: &toml::Table) -> Result<notify::Target, String> {
+        if let Some(k) = table.keys().find(|k| !["type", "url", "room", "token"].contains(&&k[..])) {
+            let key = format!("{}.{}", key, k);
+            return Err(self.error(&key, format!("unknown key {}", self.qualified(&key))));
+        }
+        let string = |name: &str| {
+            let key = format!("{}.{}", key, name);
+            match table.get(name) {
+                Some(toml::Value::String(s)) => self.interpolate(&key, s).map(Some),
+                Some(v) => Err(self.mismatch(&key, "a string", v)),
+                None => Ok(None)
+            }
+        };
+        let required = |name: &str| string(name)?.ok_or_else(|| self.error(key, format!("{} has no {}", self.qualified(key), name)));
+        let kind: notify::Kind = required("type")?.parse().map_err(|e| self.error(&format!("{}.type", key), e))?;
+        // Only Matrix posts as a user to a room, the others post to a webhook
+        let (room, token) = match kind {
+            notify::Kind::Matrix => (Some(required("room")?), Some(required("token")?)),
+            _ => (string("room")?, string("token")?)
+        };
+        Ok(notify::Target { kind, url: required("url")?, room, token })
+    }
+}
+
+const KEYS: [&str; 13] = ["repo-name", "format", "omit-author", "header-patterns", "group-by", "group-titles", "categories", "hooks",
+                          "template", "changelog", "output", "exclude-labels", "notify"];
+
+// Keys renamed between releases, old name first; the names with underscores were ignored without
+// a trace before unknown keys were rejected
+const RENAMED: [(&str, &str); 6] = [("repo_name", "repo-name"), ("omit_author", "omit-author"), ("header_patterns", "header-patterns"),
+                                    ("group_by", "group-by"), ("group_titles", "group-titles"), ("exclude_labels", "exclude-labels")];
+
+fn merge(settings: &mut Settings, source: &Source, table: &toml::Table) -> Result<(), String> {
+    // Misspelled keys would otherwise be ignored without a trace; profiles can't be nested
+    let known = |k: &str| KEYS.contains(&k) || k == "profile" && source.section.is_empty();
+    if let Some(key) = table.keys().find(|k| !known(k)) {
+        return Err(source.error(key, match RENAMED.iter().find(|&&(old, _)| old == key) {
+            Some(&(_, new)) => format!("unknown key {}, renamed to {}, see `config migrate`", source.qualified(key), new),
+            None => format!("unknown key {}", source.qualified(key))
+        }));
+    }
+    if let Some(repo_name) = source.string(table, "repo-name")? {
+        settings.repo_name = Some(repo_name);
+    }
+    if let Some(format) = source.string(table, "format")? {
+        settings.format = Some(format.parse().map_err(|e| source.error("format", e))?);
+    }
+    match table.get("omit-author") {
+        Some(&toml::Value::Boolean(b)) => settings.omit_author = Some(b),
+        Some(v) => return Err(source.mismatch("omit-author", "a boolean", v)),
+        None => {}
+    }
+    if let Some(patterns) = source.strings(table, "header-patterns")? {
+        settings.header_patterns = patterns.iter().map(|p| {
+            Regex::new(p).map_err(|e| source.error("header-patterns", format!("invalid header pattern {}: {}", p, e)))
+        }).collect::<Result<_, _>>()?;
+    }
+    if let Some(group_by) = source.string(table, "group-by")? {
+        settings.group_by = Some(group_by.parse().map_err(|e| source.error("group-by", e))?);
+    }
+    if table.contains_key("group-titles") {
+        settings.group_titles = source.titles(table, "group-titles")?;
+    }
+    if table.contains_key("categories") {
+        settings.categories = source.titles(table, "categories")?;
+    }
+    match table.get("hooks") {
+        Some(toml::Value::Table(hook_table)) => {
+            for (stage, cmd) in hook_table {
+                let key = format!("hooks.{}", stage);
+                if !hooks::STAGES.iter().any(|s| s.name() == stage) {
+                    return Err(source.error(&key, format!("unknown hook {}", stage)));
+                }
+                match *cmd {
+                    toml::Value::String(ref cmd) => { settings.hooks.insert(stage.clone(), source.interpolate(&key, cmd)?); }
+                    ref v => return Err(source.mismatch(&key, "a string", v))
+                }
+            }
+        }
+        Some(v) => return Err(source.mismatch("hooks", "a table", v)),
+        None => {}
+    }
+    if let Some(template) = source.string(table, "template")? {
+        settings.template = Some(template);
+    }
+    if let Some(changelog) = source.string(table, "changelog")? {
+        settings.changelog = Some(changelog);
+    }
+    if let Some(output) = source.string(table, "output")? {
+        settings.output = Some(output);
+    }
+    if let Some(labels) = source.strings(table, "exclude-labels")? {
+        settings.exclude_labels = labels;
+    }
+    match table.get("notify") {
+        Some(toml::Value::Table(targets)) => {
+            for (name, target) in targets {
+                let key = format!("notify.{}", name);
+                match *target {
+                    toml::Value::Table(ref target) => { settings.notify.insert(name.clone(), source.target(&key, target)?); }
+                    ref v => return Err(source.mismatch(&key, "a table", v))
+                }
+            }
+        }
+        Some(v) => return Err(source.mismatch("notify", "a table", v)),
+        None => {}
+    }
+    match table.get("profile") {
+        Some(toml::Value::Table(profiles)) => {
+            if let Some((name, v)) = profiles.iter().find(|&(_, v)| !v.is_table()) {
+                return Err(source.mismatch(&format!("profile.{}", name), "a table", v));
+            }
+        }
+        Some(v) => return Err(source.mismatch("profile", "a table", v)),
+        None => {}
+    }
+    Ok(())
+}
+
+// The files settings are read from, in order: a file given explicitly, which has to exist,
+// replaces the others; otherwise the global file is read first, so that the repository file
+// can override it
+pub fn paths(explicit: Option<&Path>, repo_dir: Option<&Path>) -> Vec<PathBuf> {
+    match explicit {
+        Some(path) => vec![path.to_path_buf()],
+        None => global_path().into_iter().chain(repo_dir.map(|d| d.join(FILE_NAME))).collect()
+    }
+}
+
+// Values given on the command line as key=value, where the value is read as in the files and taken
+// as a string if it isn't valid there, e.g. omit-author=true, format=json or profile.ci.exclude-labels=[]
+fn overrides(pairs: &[String]) -> Result<toml::Table, String> {
+    let mut table = toml::Table::new();
+    for pair in pairs {
+        let (key, value) = pair.split_once('=').ok_or_else(|| format!("--set expects key=value, found {}", pair))?;
+        let value = match format!("value = {}", value).parse::<toml::Table>() {
+            Ok(mut parsed) => parsed.remove("value").unwrap(),
+            Err(_) => toml::Value::String(value.to_string())
+        };
+        let parts: Vec<&str> = key.split('.').map(|part| part.trim()).collect();
+        let mut current = &mut table;
+        for (i, part) in parts.iter().enumerate() {
+            if i == parts.len() - 1 {
+                current.insert(part.to_string(), value);
+                break;
+            }
+            let entry = current.entry(part.to_string()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            current = entry.as_table_mut()
+                .ok_or_else(|| format!("--set {} conflicts with --set {}", key, parts[..=i].join(".")))?;
+        }
+    }
+    Ok(table)
+}
+
+// Returns the settings along with the files they were read from; the values given with --set
+// take precedence over the files, within profiles too
+pub fn load_files(explicit: Option<&Path>, repo_dir: Option<&Path>, set: &[String]) -> Result<(Settings, Vec<PathBuf>), String> {
+    let mut files = Vec::new();
+    for path in paths(explicit, repo_dir) {
+        match read(&path)? {
+            Some((text, table)) => files.push((path, text, table)),
+            None if explicit.is_some() => return Err(format!("config file {} not found", path.display())),
+            None => {}
+        }
+    }
+
+    let mut settings = Settings::default();
+    for (path, text, table) in &files {
+        merge(&mut settings, &Source { path, text, section: "" }, table)?;
+    }
+    // A profile overrides the values outside of profiles in all files, and its tables in
+    // different files are merged like those
+    let base = settings.clone();
+    for (path, text, table) in &files {
+        if let Some(toml::Value::Table(profiles)) = table.get("profile") {
+            for (name, profile) in profiles {
+                let section = format!("profile.{}", name);
+                let merged = settings.profiles.entry(name.clone()).or_insert_with(|| base.clone());
+                if let toml::Value::Table(ref profile) = *profile {
+                    merge(merged, &Source { path, text, section: &section }, profile)?;
+                }
+            }
+        }
+    }
+
+    let mut set = overrides(set)?;
+    let set_profiles = set.remove("profile");
+    let path = Path::new("--set");
+    let top = Source { path, text: "", section: "" };
+    merge(&mut settings, &top, &set)?;
+    for profile in settings.profiles.values_mut() {
+        merge(profile, &top, &set)?;
+    }
+    match set_profiles {
+        Some(toml::Value::Table(profiles)) => {
+            let base = Settings { profiles: BTreeMap::new(), ..settings.clone() };
+            for (name, profile) in &profiles {
+                let section = format!("profile.{}", name);
+                let merged = settings.profiles.entry(name.clone()).or_insert_with(|| base.clone());
+                match *profile {
+                    toml::Value::Table(ref profile) => merge(merged, &Source { path, text: "", section: &section }, profile)?,
+                    ref v => return Err(top.mismatch(&section, "a table", v))
+                }
+            }
+        }
+        Some(v) => return Err(top.mismatch("profile", "a table", &v)),
+        None => {}
+    }
+    Ok((settings, files.into_iter().map(|(path, _, _)| path).collect()))
+}
+
+pub fn load(explicit: Option<&Path>, repo_dir: Option<&Path>, set: &[String]) -> Result<Settings, String> {
+    load_files(explicit, repo_dir, set).map(|(settings, _)| settings)
+}
+
+// The parts of a dotted key with their offsets in the line, or nothing for quoted keys, which
+// are left alone
+fn key_parts(line: &str, start: usize, end: usize) -> Option<Vec<(usize, &str)>> {
+    let key = &line[start..end];
+    if key.contains('"') || key.contains('\'') {
+        return None;
+    }
+    let mut offset = start;
+    Some(key.split('.').map(|part| {
+        let trimmed = part.trim();
+        let result = (offset + part.len() - part.trim_start().len(), trimmed);
+        offset += part.len() + 1;
+        result
+    }).collect())
+}
+
+// Renames old keys of the settings and of profiles in the text of a file, keeping comments and
+// formatting; returns the new text and the renames done, with their lines
+fn rename_keys(text: &str) -> (String, Vec<(usize, &'static str, &'static str)>) {
+    let mut result = String::new();
+    let mut renames = Vec::new();
+    let mut section: Vec<String> = Vec::new();
+    let mut in_string = false;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        // Lines of multi-line strings aren't keys
+        let was_in_string = in_string;
+        if (line.matches("\"\"\"").count() + line.matches("'''").count()) % 2 == 1 {
+            in_string = !in_string;
+        }
+        let trimmed = line.trim_start();
+        let header = trimmed.starts_with('[') && !trimmed.starts_with("[[");
+        let start = line.len() - trimmed.len() + if header { 1 } else { 0 };
+        let end = match (header, line.find(if header { ']' } else { '=' })) {
+            (_, Some(end)) if !was_in_string && !trimmed.starts_with('#') => end,
+            _ => {
+                result.push_str(line);
+                continue;
+            }
+        };
+        let parts = match key_parts(line, start, end) {
+            Some(parts) => parts,
+            None => {
+                result.push_str(line);
+                continue;
+            }
+        };
+
+        // Setting names come first in the path, or after profile.NAME
+        let base = if header { 0 } else { section.len() };
+        let mut path: Vec<&str> = if header { Vec::new() } else { section.iter().map(|s| &s[..]).collect() };
+        path.extend(parts.iter().map(|&(_, part)| part));
+        let index = if path.first() == Some(&"profile") { 2 } else { 0 };
+        let mut line = line.to_string();
+        if index >= base && index < path.len() {
+            if let Some(&(old, new)) = RENAMED.iter().find(|&&(old, _)| old == path[index]) {
+                let offset = parts[index - base].0;
+                line.replace_range(offset..offset + old.len(), new);
+                renames.push((i + 1, old, new));
+                path[index] = new;
+            }
+        }
+        if header {
+            section = path.iter().map(|p| p.to_string()).collect();
+        }
+        result.push_str(&line);
+    }
+    (result, renames)
+}
+
+// Renames old keys in the file, keeping the original next to it with a .bak extension; returns
+// the renames done as messages
+pub fn migrate(path: &Path) -> Result<Vec<String>, String> {
+    let (text, _) = read(path)?.ok_or_else(|| format!("config file {} not found", path.display()))?;
+    let (migrated, renames) = rename_keys(&text);
+    if renames.is_empty() {
+        return Ok(Vec::new());
+    }
+    // A file may set both the old key and the new one
+    migrated.parse::<toml::Table>().map_err(|e| format!("cannot migrate {}: {}", path.display(), e))?;
+
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(".bak");
+    fs::copy(path, &backup).map_err(|e| format!("cannot back up {}: {}", path.display(), e))?;
+    fs::write(path, migrated).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    Ok(renames.iter().map(|&(line, old, new)| format!("{}:{}: renamed {} to {}", path.display(), line, old, new)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Loads the text as an explicitly given file, with errors naming it "config.toml"
+    fn load_text(name: &str, text: &str) -> Result<Settings, String> {
+        let path = env::temp_dir().join(format!("git-pull-requests-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let result = load(Some(&path), None, &[]);
+        fs::remove_file(&path).unwrap();
+        result.map_err(|e| e.replace(&path.display().to_string(), "config.toml"))
+    }
+
+    #[test]
+    fn parses_settings() {
+        let settings = load_text("parse", r#"
+repo-name = "acme/widgets"
+omit-author = true
+header-patterns = ['^Merged !(?P<id>\d+)']
+group-by = "label"
+exclude-labels = ["internal", "ci"]
+
+[categories]
+Feat = "Features"
+
+[hooks]
+post-generate = "cat"
+"#).unwrap();
+        assert_eq!(settings.repo_name.as_deref(), Some("acme/widgets"));
+        assert_eq!(settings.omit_author, Some(true));
+        assert_eq!(settings.header_patterns.len(), 1);
+        assert!(settings.group_by == Some(GroupBy::Label));
+        assert_eq!(settings.exclude_labels, vec!["internal", "ci"]);
+        assert_eq!(settings.categories, vec![("feat".to_string(), "Features".to_string())]);
+        assert_eq!(settings.hooks.get("post-generate").map(|c| &c[..]), Some("cat"));
+        assert!(settings.format.is_none() && settings.template.is_none() && settings.profiles.is_empty());
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let error = |name, text| load_text(name, text).err().unwrap();
+        assert_eq!(error("unknown", "repo-name = \"x\"\n\nomit-authors = true\n"), "config.toml:3: unknown key omit-authors");
+        assert_eq!(error("renamed", "[profile.ci]\ngroup_by = \"label\"\n"),
+                   "config.toml:2: unknown key profile.ci.group_by, renamed to group-by, see `config migrate`");
+        assert_eq!(error("type", "[profile.ci]\nomit-author = \"yes\"\n"),
+                   "config.toml:2: profile.ci.omit-author must be a boolean, found string");
+        assert_eq!(error("hook", "[hooks]\npost-generate = \"cat\"\npre-build = \"make\"\n"), "config.toml:3: unknown hook pre-build");
+        assert_eq!(error("nested", "[profile.ci.profile.x]\nformat = \"json\"\n"),
+                   "config.toml:1: unknown key profile.ci.profile");
+        assert_eq!(error("grouping", "\ngroup-by = \"author\"\n"), "config.toml:2: unknown grouping: author");
+        assert!(error("syntax", "repo-name = \n").starts_with("config.toml: "));
+    }
+
+    #[test]
+    fn interpolates_environment_variables() {
+        env::set_var("GIT_PULL_REQUESTS_TEST_HOOK", "notify");
+        let settings = load_text("env", "[hooks]\npost-publish = \"${GIT_PULL_REQUESTS_TEST_HOOK} --all\"\n").unwrap();
+        assert_eq!(settings.hooks.get("post-publish").map(|c| &c[..]), Some("notify --all"));
+
+        assert_eq!(load_text("unset", "repo-name = \"x\"\n[profile.ci]\ntemplate = \"${GIT_PULL_REQUESTS_TEST_UNSET}/notes.hbs\"\n").err(),
+                   Some("config.toml:3: profile.ci.template refers to environment variable GIT_PULL_REQUESTS_TEST_UNSET, which is not set".to_string()));
+    }
+
+    #[test]
+    fn selects_profiles() {
+        let settings = load_text("profiles", r#"
+format = "json"
+exclude-labels = ["internal"]
+
+[profile.public]
+format = "markdown"
+
+[profile.internal]
+exclude-labels = []
+"#).unwrap();
+        let public = settings.clone().profile("public").unwrap();
+        assert!(matches!(public.format, Some(OutputFormat::Markdown)));
+        assert_eq!(public.exclude_labels, vec!["internal"]);
+        let internal = settings.clone().profile("internal").unwrap();
+        assert!(matches!(internal.format, Some(OutputFormat::Json)));
+        assert!(internal.exclude_labels.is_empty());
+        assert_eq!(settings.profile("beta").err(), Some("unknown profile beta".to_string()));
+    }
+
+    #[test]
+    fn parses_notification_targets() {
+        env::set_var("GIT_PULL_REQUESTS_TEST_MATRIX_TOKEN", "secret");
+        let settings = load_text("notify", r#"
+[notify.chat]
+type = "matrix"
+url = "https://matrix.example.org"
+room = "!releases:example.org"
+token = "${GIT_PULL_REQUESTS_TEST_MATRIX_TOKEN}"
+
+[notify.team]
+type = "teams"
+url = "https://example.webhook.office.com/webhookb2/x"
+"#).unwrap();
+        assert_eq!(settings.notify.get("chat"), Some(&notify::Target {
+            kind: notify::Kind::Matrix,
+            url: "https://matrix.example.org".to_string(),
+            room: Some("!releases:example.org".to_string()),
+            token: Some("secret".to_string())
+        }));
+        assert_eq!(settings.notify.get("team").map(|t| t.kind), Some(notify::Kind::Teams));
+
+        let error = |name, text| load_text(name, text).err().unwrap();
+        assert_eq!(error("notify-room", "[notify.chat]\ntype = \"matrix\"\nurl = \"https://matrix.example.org\"\n"),
+                   "config.toml:1: notify.chat has no room");
+        assert_eq!(error("notify-type", "[notify.chat]\ntype = \"slack\"\n"), "config.toml:2: unknown notification type: slack");
+        assert_eq!(error("notify-key", "[notify.chat]\ntype = \"teams\"\nchannel = \"x\"\n"),
+                   "config.toml:3: unknown key notify.chat.channel");
+    }
+
+    #[test]
+    fn renames_old_keys() {
+        let (text, renames) = rename_keys(r#"# Release notes
+repo_name = "acme/widgets"
+format = "json"  # group_by = "label"
+header-patterns = [
+  '^Merged (?P<id>\d+) repo_name =',
+]
+
+[group_titles]
+repo_name = "Not a setting"
+
+[profile.repo_name]
+exclude_labels = ["internal"]
+hooks.post_generate = "cat"
+
+[profile.ci.group_titles]
+x = "y"
+"#);
+        assert_eq!(text, r#"# Release notes
+repo-name = "acme/widgets"
+format = "json"  # group_by = "label"
+header-patterns = [
+  '^Merged (?P<id>\d+) repo_name =',
+]
+
+[group-titles]
+repo_name = "Not a setting"
+
+[profile.repo_name]
+exclude-labels = ["internal"]
+hooks.post_generate = "cat"
+
+[profile.ci.group-titles]
+x = "y"
+"#);
+        assert_eq!(renames, vec![(2, "repo_name", "repo-name"), (8, "group_titles", "group-titles"),
+                                 (12, "exclude_labels", "exclude-labels"), (15, "group_titles", "group-titles")]);
+        assert!(rename_keys("repo-name = \"x\"\n\"repo_name\" = \"y\"\n").1.is_empty());
+    }
+
+    #[test]
+    fn migrates_files_with_a_backup() {
+        let path = env::temp_dir().join(format!("git-pull-requests-migrate-{}.toml", std::process::id()));
+        let backup = path.with_extension("toml.bak");
+        fs::write(&path, "omit_author = true\n").unwrap();
+        assert_eq!(migrate(&path).unwrap(), vec![format!("{}:1: renamed omit_author to omit-author", path.display())]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "omit-author = true\n");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "omit_author = true\n");
+        assert!(migrate(&path).unwrap().is_empty());
+
+        fs::write(&path, "omit_author = true\nomit-author = false\n").unwrap();
+        assert!(migrate(&path).unwrap_err().starts_with(&format!("cannot migrate {}", path.display())));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "omit_author = true\nomit-author = false\n");
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn overrides_settings_with_set() {
+        let path = env::temp_dir().join(format!("git-pull-requests-set-{}.toml", std::process::id()));
+        fs::write(&path, "format = \"json\"\nomit-author = true\n[profile.ci]\nformat = \"table\"\nrepo-name = \"a/b\"\n").unwrap();
+        let set = |pairs: &[&str]| load(Some(&path), None, &pairs.iter().map(|p| p.to_string()).collect::<Vec<_>>());
+
+        let settings = set(&["format=markdown", "omit-author=false", "exclude-labels=[\"ci\"]", "hooks.post-generate=cat",
+                             "profile.ci.repo-name=c/d", "profile.new.group-by=label"]).unwrap();
+        assert!(matches!(settings.format, Some(OutputFormat::Markdown)));
+        assert_eq!(settings.omit_author, Some(false));
+        assert_eq!(settings.exclude_labels, vec!["ci"]);
+        assert_eq!(settings.hooks.get("post-generate").map(|c| &c[..]), Some("cat"));
+        let ci = settings.clone().profile("ci").unwrap();
+        assert!(matches!(ci.format, Some(OutputFormat::Markdown)));
+        assert_eq!(ci.repo_name.as_deref(), Some("c/d"));
+        let new = settings.profile("new").unwrap();
+        assert!(new.group_by == Some(GroupBy::Label) && new.omit_author == Some(false));
+
+        let error = |pairs: &[&str]| set(pairs).err().unwrap();
+        assert_eq!(error(&["omit-authors=true"]), "--set: unknown key omit-authors");
+        assert_eq!(error(&["omit-author=1"]), "--set: omit-author must be a boolean, found integer");
+        assert_eq!(error(&["profile.ci.profile.x=1"]), "--set: unknown key profile.ci.profile");
+        assert_eq!(error(&["profile.ci=1"]), "--set: profile.ci must be a table, found integer");
+        assert_eq!(error(&["format"]), "--set expects key=value, found format");
+        assert_eq!(error(&["hooks=1", "hooks.post-generate=cat"]), "--set hooks.post-generate conflicts with --set hooks");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn requires_explicit_files_to_exist() {
+        let path = env::temp_dir().join("git-pull-requests-missing.toml");
+        assert_eq!(load(Some(&path), None, &[]).err(), Some(format!("config file {} not found", path.display())));
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use git2::{self, Repository};
+use serde_json::{Map, Value};
+
+use {PullRequestInfo, format_time_utc, parallel_chunks, pull_request_diff};
+use orgs::{self, OrgMap};
+
+const DAY: i64 = 24 * 60 * 60;
+const TOP_PATHS: usize = 20;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+fn percent(n: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { n as f64 * 100.0 / total as f64 }
+}
+
+fn write_counts(out: &mut String, title: &str, counts: BTreeMap<String, usize>, total: usize) {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    writeln!(out, "{}:", title).unwrap();
+    for (name, count) in counts {
+        writeln!(out, "  {}: {} ({:.1}%)", name, count, percent(count, total)).unwrap();
+    }
+}
+
+fn week_start(seconds: i64) -> i64 {
+    let days = seconds / DAY;
+    // 1970-01-01 was a Thursday, weeks start on Monday
+    (days - (days + 3) % 7) * DAY
+}
+
+// Counts merged pull requests per week, including the empty weeks between the first and the last one
+fn weekly(pull_requests: &[PullRequestInfo]) -> Vec<(i64, usize)> {
+    let mut counts = BTreeMap::new();
+    for pr in pull_requests {
+        *counts.entry(week_start(pr.time)).or_insert(0) += 1;
+    }
+    let (first, last) = match (counts.keys().next(), counts.keys().next_back()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return Vec::new()
+    };
+    let mut result = Vec::new();
+    let mut week = first;
+    while week <= last {
+        result.push((week, counts.get(&week).cloned().unwrap_or(0)));
+        week += 7 * DAY;
+    }
+    result
+}
+
+fn write_timeline(out: &mut String, weeks: &[(i64, usize)]) {
+    let max = weeks.iter().map(|&(_, n)| n).max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+    let sparkline: String = weeks.iter().map(|&(_, n)| SPARKS[n * (SPARKS.len() - 1) / max]).collect();
+    writeln!(out, "Pull requests per week: {}", sparkline).unwrap();
+    for &(week, n) in weeks {
+        writeln!(out, "  {} {:>4} {}", format_time_utc(week, "%Y-%m-%d"), n,
+                 std::iter::repeat_n('#', n * 40 / max).collect::<String>()).unwrap();
+    }
+}
+
+fn by_author(pull_requests: &[PullRequestInfo]) -> BTreeMap<String, usize> {
+    let mut result = BTreeMap::new();
+    for pr in pull_requests {
+        *result.entry(pr.author.clone()).or_insert(0) += 1;
+    }
+    result
+}
+
+fn by_org(pull_requests: &[PullRequestInfo], org_map: &OrgMap) -> BTreeMap<String, usize> {
+    let mut result: BTreeMap<String, usize> = orgs::contributions(pull_requests, org_map).into_iter()
+        .map(|(org, c)| (org, c.pull_requests))
+        .collect();
+    let affiliated = result.values().sum::<usize>();
+    if affiliated < pull_requests.len() {
+        result.insert("Unaffiliated".into(), pull_requests.len() - affiliated);
+    }
+    result
+}
+
+pub struct PathCounts {
+    files: BTreeMap<String, usize>,
+    directories: BTreeMap<String, usize>
+}
+
+fn changed_in(repo: &Repository, sha: &str) -> Result<(BTreeSet<String>, BTreeSet<String>), git2::Error> {
+    let diff = pull_request_diff(repo, sha)?;
+    let mut files = BTreeSet::new();
+    let mut directories = BTreeSet::new();
+    for delta in diff.deltas() {
+        let path = match delta.new_file().path().or(delta.old_file().path()) {
+            Some(path) => path,
+            None => continue
+        };
+        files.insert(path.to_string_lossy().into_owned());
+        let mut dir = path.parent();
+        while let Some(d) = dir {
+            if !d.as_os_str().is_empty() {
+                directories.insert(format!("{}/", d.to_string_lossy()));
+            }
+            dir = d.parent();
+        }
+    }
+    Ok((files, directories))
+}
+
+// Every pull request counts once per file and once per directory containing changed files;
+// repositories can't be shared between threads, so every thread opens its own
+pub fn changed_paths(repo_path: &Path, pull_requests: &[PullRequestInfo], jobs: usize) -> Result<PathCounts, String> {
+    let repo_path = repo_path.to_path_buf();
+    let shas: Vec<String> = pull_requests.iter().map(|pr| pr.sha.clone()).collect();
+    let results = parallel_chunks(shas, jobs, move |chunk| {
+        let repo = match Repository::open(&repo_path) {
+            Ok(repo) => repo,
+            Err(e) => return chunk.iter().map(|_| Err(format!("cannot open repository: {}", e))).collect()
+        };
+        chunk.iter().map(|sha| changed_in(&repo, sha).map_err(|e| format!("cannot get diff of {}: {}", sha, e))).collect()
+    });
+
+    let mut counts = PathCounts { files: BTreeMap::new(), directories: BTreeMap::new() };
+    for result in results {
+        let (files, directories) = result?;
+        for file in files {
+            *counts.files.entry(file).or_insert(0) += 1;
+        }
+        for dir in directories {
+            *counts.directories.entry(dir).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+fn top(counts: &BTreeMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut result: Vec<(&str, usize)> = counts.iter().map(|(p, &n)| (&p[..], n)).collect();
+    result.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    result.truncate(TOP_PATHS);
+    result
+}
+
+fn write_top(out: &mut String, title: &str, counts: &BTreeMap<String, usize>) {
+    writeln!(out, "{}:", title).unwrap();
+    for (path, n) in top(counts) {
+        writeln!(out, "  {}: {}", path, n).unwrap();
+    }
+}
+
+fn top_json(counts: &BTreeMap<String, usize>) -> Value {
+    Value::Array(top(counts).into_iter().map(|(path, n)| json!({"path": path, "pull_requests": n})).collect())
+}
+
+pub fn report(pull_requests: &[PullRequestInfo], org_map: Option<&OrgMap>, paths: Option<&PathCounts>) -> String {
+    let mut out = String::new();
+    let total = pull_requests.len();
+    writeln!(out, "Pull requests: {}", total).unwrap();
+
+    let authors = by_author(pull_requests);
+    writeln!(out, "Authors: {}", authors.len()).unwrap();
+    write_counts(&mut out, "Pull requests by author", authors, total);
+
+    if let Some(org_map) = org_map {
+        write_counts(&mut out, "Pull requests by organization", by_org(pull_requests, org_map), total);
+    }
+
+    write_timeline(&mut out, &weekly(pull_requests));
+
+    if let Some(paths) = paths {
+        write_top(&mut out, "Most changed directories", &paths.directories);
+        write_top(&mut out, "Most changed files", &paths.files);
+    }
+    out
+}
+
+pub fn to_json(pull_requests: &[PullRequestInfo], org_map: Option<&OrgMap>, paths: Option<&PathCounts>) -> Value {
+    let mut obj = Map::new();
+    obj.insert("pull_requests".to_string(), json!(pull_requests.len()));
+    obj.insert("authors".to_string(), json!(by_author(pull_requests)));
+    if let Some(org_map) = org_map {
+        obj.insert("organizations".to_string(), json!(by_org(pull_requests, org_map)));
+    }
+    let weeks: Vec<Value> = weekly(pull_requests).into_iter().map(|(week, n)| {
+        json!({"week": format_time_utc(week, "%Y-%m-%d"), "pull_requests": n})
+    }).collect();
+    obj.insert("weeks".to_string(), Value::Array(weeks));
+    if let Some(paths) = paths {
+        obj.insert("directories".to_string(), top_json(&paths.directories));
+        obj.insert("files".to_string(), top_json(&paths.files));
+    }
+    Value::Object(obj)
+}
@@ -0,0 +1,63 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use handlebars::Handlebars;
+use serde_json::Value;
+
+use {PullRequestInfo, normalize_json};
+
+// Values describing the whole release, available at the top level of the template context
+#[derive(Default)]
+pub struct Document {
+    pub repo_name: Option<String>,
+    pub version: Option<String>,
+    // The tag the range starts at, or the latest one before its start
+    pub previous_version: Option<String>,
+    pub release_date: String,
+    pub compare_url: Option<String>,
+    pub range: String
+}
+
+// The template is rendered once with the document values, `repo` (the same as `repo_name`)
+// and `pull_requests` in its context; every pull request also carries `repo` so that it is
+// available inside `{{#each}}` blocks, and `short_sha` next to the full `sha`
+pub fn render(path: &Path, pull_requests: &[PullRequestInfo], document: &Document,
+              normalize: bool) -> Result<String, String> {
+    let mut source = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut source))
+        .map_err(|e| format!("cannot read template {}: {}", path.display(), e))?;
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_template_string("changelog", source)
+        .map_err(|e| format!("invalid template {}: {}", path.display(), e))?;
+
+    let repo = json!(document.repo_name.as_ref().map_or("", |r| &r[..]));
+    let items: Vec<Value> = pull_requests.iter().map(|pr| {
+        let mut json = match pr.to_json() {
+            Value::Object(mut obj) => {
+                obj.insert("repo".to_string(), repo.clone());
+                obj.insert("short_sha".to_string(), json!(pr.sha[..7]));
+                Value::Object(obj)
+            }
+            other => other
+        };
+        if normalize {
+            normalize_json(&mut json);
+        }
+        json
+    }).collect();
+
+    let context = json!({
+        "repo": repo,
+        "repo_name": repo,
+        "version": document.version,
+        "previous_version": document.previous_version,
+        "release_date": document.release_date,
+        "compare_url": document.compare_url,
+        "range": document.range,
+        "pull_requests": items
+    });
+    handlebars.render("changelog", &context)
+        .map_err(|e| format!("cannot render template {}: {}", path.display(), e))
+}
@@ -0,0 +1,175 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use auth;
+use forge;
+use github::GitHub;
+
+// The project's releases carry a binary for each platform, named like
+// git-pull-requests-x86_64-linux, and a SHA256SUMS file listing their checksums
+pub const REPOSITORY: &str = "netvl/git-pull-requests";
+const CHECKSUMS: &str = "SHA256SUMS";
+
+pub fn asset_name() -> String {
+    format!("git-pull-requests-{}-{}{}", env::consts::ARCH, env::consts::OS, env::consts::EXE_SUFFIX)
+}
+
+// Numeric parts of versions like v1.2.0 or 1.2.0-rc.1, compared in order; prereleases don't
+// show up as the latest release, so their suffixes are left out
+fn version_parts(version: &str) -> Vec<u64> {
+    let version = version.trim_start_matches('v');
+    let version = version.split('-').next().unwrap_or("");
+    version.split('.').map_while(|part| part.parse().ok()).collect()
+}
+
+pub fn is_newer(version: &str, current: &str) -> bool {
+    version_parts(version) > version_parts(current)
+}
+
+// SHA-256 as in FIPS 180-4, to check downloads on machines which may lack sha256sum
+fn sha256(data: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
+    ];
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+    h.iter().map(|x| format!("{:08x}", x)).collect()
+}
+
+// Lines of SHA256SUMS are like "<checksum>  <name>", with a * before binary file names
+fn checksum_of(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (checksum, file) = line.trim().split_once(char::is_whitespace)?;
+        if file.trim().trim_start_matches('*') == name { Some(checksum.to_lowercase()) } else { None }
+    })
+}
+
+fn asset_url(release: &Value, name: &str) -> Option<String> {
+    release.get("assets").and_then(|a| a.as_array()).map_or(&[][..], |a| &a[..]).iter()
+        .find(|asset| asset.get("name").and_then(|n| n.as_str()) == Some(name))
+        .and_then(|asset| asset.get("browser_download_url")).and_then(|u| u.as_str()).map(|u| u.to_string())
+}
+
+// The new binary is written next to the current one and renamed over it, which replaces it
+// atomically; Windows doesn't allow that for a running executable, but does allow moving it away
+fn replace(exe: &Path, binary: &[u8]) -> Result<(), String> {
+    let with_suffix = |suffix: &str| {
+        let mut path = exe.as_os_str().to_os_string();
+        path.push(suffix);
+        PathBuf::from(path)
+    };
+    let new = with_suffix(".new");
+    fs::write(&new, binary).map_err(|e| format!("cannot write {}: {}", new.display(), e))?;
+    let permissions = fs::metadata(exe).map_err(|e| format!("cannot read {}: {}", exe.display(), e))?.permissions();
+    fs::set_permissions(&new, permissions).map_err(|e| format!("cannot set permissions of {}: {}", new.display(), e))?;
+    if cfg!(windows) {
+        let old = with_suffix(".old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).map_err(|e| format!("cannot move {} away: {}", exe.display(), e))?;
+    }
+    fs::rename(&new, exe).map_err(|e| format!("cannot replace {}: {}", exe.display(), e))
+}
+
+// Installs the latest release from the project's repository on the given host, which may be
+// a GitHub Enterprise mirror, unless it is not newer than the running version; returns the
+// version of the release
+pub fn self_update(host: &str, check_only: bool) -> Result<Option<String>, String> {
+    let current = env!("CARGO_PKG_VERSION");
+    let github = GitHub::new(host, REPOSITORY, auth::token(host));
+    let release = github.latest_release().map_err(|e| format!("cannot find the latest release: {}", e))?;
+    let version = release.get("tag_name").and_then(|t| t.as_str())
+        .ok_or_else(|| format!("the latest release of {} has no tag", REPOSITORY))?;
+    if !is_newer(version, current) {
+        return Ok(None);
+    }
+    if check_only {
+        return Ok(Some(version.to_string()));
+    }
+
+    let name = asset_name();
+    let binary_url = asset_url(&release, &name).ok_or_else(|| format!("release {} has no binary {}", version, name))?;
+    let checksums_url = asset_url(&release, CHECKSUMS).ok_or_else(|| format!("release {} has no {}", version, CHECKSUMS))?;
+    let checksums = String::from_utf8_lossy(&forge::download(&checksums_url, &[])?).into_owned();
+    let expected = checksum_of(&checksums, &name).ok_or_else(|| format!("{} of {} has no checksum of {}", CHECKSUMS, version, name))?;
+    let binary = forge::download(&binary_url, &[])?;
+    let actual = sha256(&binary);
+    if actual != expected {
+        return Err(format!("checksum mismatch for {}: expected {}, got {}", name, expected, actual));
+    }
+
+    let exe = env::current_exe().and_then(|exe| exe.canonicalize())
+        .map_err(|e| format!("cannot find the running executable: {}", e))?;
+    replace(&exe, &binary)?;
+    Ok(Some(version.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_sha256() {
+        assert_eq!(sha256(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(sha256(&[b'a'; 1000]), "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+    }
+
+    #[test]
+    fn finds_checksums_and_newer_versions() {
+        let checksums = "abc123  git-pull-requests-x86_64-linux\nDEF456 *git-pull-requests-aarch64-macos\n";
+        assert_eq!(checksum_of(checksums, "git-pull-requests-aarch64-macos").as_deref(), Some("def456"));
+        assert_eq!(checksum_of(checksums, "git-pull-requests-x86_64-windows.exe"), None);
+        assert!(is_newer("v0.1.0", "0.0.1"));
+        assert!(is_newer("v0.0.10", "0.0.9"));
+        assert!(!is_newer("v0.0.1", "0.0.1"));
+        assert!(!is_newer("0.0.1-rc.1", "0.0.1"));
+    }
+}